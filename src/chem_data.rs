@@ -6,4 +6,10 @@ pub mod chem_eqn;
 
 pub mod dictionary;
 
+pub mod methods;
+
+pub mod quantity;
+
 pub mod real_number;
+
+pub mod smiles;
@@ -1,13 +1,14 @@
-use std::fmt::Display;
+use std::{error::Error, fmt::Display};
 
-use super::formulas::MolecularFormula;
-use crate::helper::coeff_vec::CoeffVec;
+use super::{elements::Element, formulas::MolecularFormula};
 pub use crate::helper::coeff_vec::Num;
+use crate::helper::{coeff_vec::CoeffVec, nullspace::integer_nullspace};
 
 #[derive(Default, Debug, PartialEq, Clone)]
 pub struct ChemEqn<'a> {
 	// positive coefficients are products, negative coefficients are reactants
 	specieses: CoeffVec<MolecularFormula<'a>>,
+	reversible: bool,
 }
 
 impl<'a> ChemEqn<'a> {
@@ -23,11 +24,313 @@ impl<'a> ChemEqn<'a> {
 		self.specieses.get_coeff(species)
 	}
 
-	// TODO add solve(self) -> Self
+	/// Adds the coefficient to whatever coefficient the species already has on the same side. A
+	/// species can't be on both sides of the equation, since its coefficients would cancel out, and
+	/// it must have at least one atom or a charge.
+	pub fn add_species(
+		&mut self,
+		species: MolecularFormula<'a>,
+		coeff: Num,
+	) -> Result<(), AddSpeciesError> {
+		if species.iter().next().is_none() && species.get_charge() == 0 {
+			return Err(AddSpeciesError::Empty);
+		}
+		let old_coeff = self.specieses.get_coeff(&species);
+		if old_coeff.signum() * coeff.signum() < 0 {
+			return Err(AddSpeciesError::OnBothSides {
+				species: species.to_string(),
+			});
+		}
+		let new_coeff = old_coeff
+			.checked_add(coeff)
+			.ok_or(AddSpeciesError::Overflow)?;
+		self.specieses.set_coeff(&species, new_coeff);
+		Ok(())
+	}
+
+	pub fn is_reversible(&self) -> bool {
+		self.reversible
+	}
+
+	pub fn set_reversible(&mut self, reversible: bool) {
+		self.reversible = reversible;
+	}
+
+	/// Iterates over each species in the equation along with its coefficient
+	pub fn iter(&self) -> impl Iterator<Item = &(MolecularFormula<'a>, Num)> {
+		self.specieses.iter()
+	}
+
+	/// Returns a copy of this equation with the smallest all-integer coefficients that conserve
+	/// every element as well as charge. The sign of each existing coefficient determines which
+	/// side of the equation the species must end up on; only the magnitudes are solved for.
+	pub fn balance(&self) -> Result<Self, BalanceError> {
+		let specieses: Vec<&(MolecularFormula<'a>, Num)> = self.specieses.iter().collect();
+
+		// build the element-by-species composition matrix; electrons are accounted for by the
+		// charges of the species rather than as an element
+		let mut elements: Vec<&Element> = Vec::new();
+		for (formula, _) in &specieses {
+			for (element, _) in formula.iter() {
+				if !element.is_electron() && !elements.contains(&element) {
+					elements.push(element);
+				}
+			}
+		}
+		let mut matrix: Vec<Vec<i64>> = elements
+			.iter()
+			.map(|&element| {
+				specieses
+					.iter()
+					.map(|(formula, _)| i64::from(formula.get_subscr(element)))
+					.collect()
+			})
+			.collect();
+		if specieses
+			.iter()
+			.any(|(formula, _)| formula.get_charge() != 0)
+		{
+			matrix.push(
+				specieses
+					.iter()
+					.map(|(formula, _)| i64::from(formula.get_charge()))
+					.collect(),
+			);
+		}
+
+		let mut basis =
+			integer_nullspace(&matrix, specieses.len()).ok_or(BalanceError::Overflow)?;
+		let solution = match basis.len() {
+			0 => return Err(BalanceError::NoSolution),
+			1 => basis
+				.pop()
+				.expect("There should've been exactly one solution"),
+			independent_solutions => {
+				return Err(BalanceError::MultipleSolutions {
+					independent_solutions,
+				})
+			},
+		};
+
+		// orient the solution so that the first species stays on its side, then make sure every
+		// other species did too
+		let orientation = i64::from(specieses[0].1.signum()) * solution[0].signum();
+		let mut result = Self {
+			reversible: self.reversible,
+			..Self::new()
+		};
+		for ((formula, old_coeff), new_coeff) in specieses.into_iter().zip(solution) {
+			let new_coeff = new_coeff
+				.checked_mul(orientation)
+				.and_then(|new_coeff| Num::try_from(new_coeff).ok())
+				.ok_or(BalanceError::Overflow)?;
+			if new_coeff.signum() != old_coeff.signum() {
+				return Err(BalanceError::NoSolution);
+			}
+			result.specieses.set_coeff(formula, new_coeff);
+		}
+		Ok(result)
+	}
 }
 
+/// Displays the equation in textbook notation, e.g. `CH4 + 2O2 -> CO2 + 2H2O`. The species on each
+/// side are listed in the order of a chemical index (see `MolecularFormula::cmp_hill`), so that
+/// equal equations display the same whatever order their species were added in.
 impl Display for ChemEqn<'_> {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		todo!();
+		let write_side = |f: &mut std::fmt::Formatter<'_>, is_products: bool| {
+			let mut side: Vec<_> = self
+				.iter()
+				.filter(|(_, coeff)| (*coeff > 0) == is_products)
+				.collect();
+			side.sort_by(|(a, _), (b, _)| a.cmp_hill(b));
+			for (i, (species, coeff)) in side.into_iter().enumerate() {
+				if i > 0 {
+					write!(f, " + ")?;
+				}
+				if coeff.unsigned_abs() != 1 {
+					write!(f, "{}", coeff.unsigned_abs())?;
+				}
+				write!(f, "{}", species)?;
+			}
+			Ok(())
+		};
+
+		write_side(f, false)?;
+		write!(f, " {} ", if self.reversible { "<=>" } else { "->" })?;
+		write_side(f, true)
+	}
+}
+
+#[derive(Debug, PartialEq)]
+pub enum BalanceError {
+	/// No choice of coefficients conserves every element with each species on its given side
+	NoSolution,
+	/// The coefficients are not determined up to scaling, e.g. because the equation is really
+	/// several independent reactions added together
+	MultipleSolutions { independent_solutions: usize },
+	/// The coefficients, or the numbers needed to find them, are too large to store
+	Overflow,
+}
+impl Display for BalanceError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			BalanceError::NoSolution => write!(f, "The equation cannot be balanced as written"),
+			BalanceError::MultipleSolutions {
+				independent_solutions,
+			} => write!(
+				f,
+				"The equation has {} independent balanced forms; it cannot be balanced uniquely",
+				independent_solutions
+			),
+			BalanceError::Overflow => {
+				write!(f, "The equation's coefficients are too large to balance it")
+			},
+		}
+	}
+}
+impl Error for BalanceError {}
+
+#[derive(Debug, PartialEq)]
+pub enum AddSpeciesError {
+	/// The species is already on the other side of the equation
+	OnBothSides { species: String },
+	/// The species has no atoms and no charge, e.g. `$H2O * 0`
+	Empty,
+	/// The species' coefficient is too large to store
+	Overflow,
+}
+impl Display for AddSpeciesError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			AddSpeciesError::OnBothSides { species } => write!(
+				f,
+				"{} is on both sides of the equation; its coefficients would cancel out",
+				species
+			),
+			AddSpeciesError::Empty => {
+				write!(f, "A species must have at least one atom or a charge")
+			},
+			AddSpeciesError::Overflow => write!(f, "A coefficient is too large"),
+		}
+	}
+}
+impl Error for AddSpeciesError {}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::chem_data::{elements::PeriodicTable, formulas::tests::formula};
+
+	fn equation<'a>(specieses: &[(MolecularFormula<'a>, Num)]) -> ChemEqn<'a> {
+		let mut result = ChemEqn::new();
+		for (species, coeff) in specieses {
+			result.specieses.set_coeff(species, *coeff);
+		}
+		result
+	}
+
+	#[test]
+	fn balances_correctly() {
+		let p_table = PeriodicTable::new_alphabetic();
+		let al = formula(&p_table, &[("Al", 1)]);
+		let bo2 = formula(&p_table, &[("Bo", 2)]);
+		let al2bo3 = formula(&p_table, &[("Al", 2), ("Bo", 3)]);
+
+		let unbalanced = equation(&[(al.clone(), -1), (bo2.clone(), -1), (al2bo3.clone(), 1)]);
+		let balanced = equation(&[(al, -4), (bo2, -3), (al2bo3, 2)]);
+		assert_eq!(unbalanced.balance(), Ok(balanced.clone()));
+		assert_eq!(balanced.balance(), Ok(balanced));
+	}
+
+	#[test]
+	fn reports_unbalanceable_equations() {
+		let p_table = PeriodicTable::new_alphabetic();
+		let al = formula(&p_table, &[("Al", 1)]);
+		let bo = formula(&p_table, &[("Bo", 1)]);
+		let albo = formula(&p_table, &[("Al", 1), ("Bo", 1)]);
+		let al2bo2 = formula(&p_table, &[("Al", 2), ("Bo", 2)]);
+
+		let no_solution = equation(&[(al.clone(), -1), (bo.clone(), 1)]);
+		assert_eq!(no_solution.balance(), Err(BalanceError::NoSolution));
+
+		let wrong_side = equation(&[(albo.clone(), -1), (al.clone(), -1), (bo.clone(), -1)]);
+		assert_eq!(wrong_side.balance(), Err(BalanceError::NoSolution));
+
+		let multiple = equation(&[(al, -1), (bo, -1), (albo, 1), (al2bo2, 1)]);
+		assert_eq!(
+			multiple.balance(),
+			Err(BalanceError::MultipleSolutions {
+				independent_solutions: 2
+			})
+		);
+
+		// huge subscripts overflow while eliminating or while finding the coefficients
+		let overflowing = [
+			[
+				(vec![("Al", 2147483291), ("Bo", 2147483279)], -1),
+				(vec![("Ch", 2147483231)], -1),
+				(vec![("Al", 2147483197), ("Ch", 2147483189)], 1),
+				(vec![("Bo", 2147483161)], 1),
+			],
+			[
+				(vec![("Al", 2147483291), ("Bo", 2147483279)], -1),
+				(vec![("Ch", 2147483231), ("Al", 2147483227)], -1),
+				(vec![("Al", 2147483197), ("Ch", 2147483189)], 1),
+				(vec![("Bo", 2147483161), ("Ch", 2147483149)], 1),
+			],
+		];
+		for specieses in overflowing {
+			let specieses: Vec<_> = specieses
+				.into_iter()
+				.map(|(parts, coeff)| (formula(&p_table, &parts), coeff))
+				.collect();
+			assert_eq!(equation(&specieses).balance(), Err(BalanceError::Overflow));
+		}
+	}
+
+	#[test]
+	fn displays_correctly() {
+		let p_table = PeriodicTable::new_alphabetic();
+		let al = formula(&p_table, &[("Al", 1)]);
+		let bo2 = formula(&p_table, &[("Bo", 2)]);
+		let al2bo3 = formula(&p_table, &[("Al", 2), ("Bo", 3)]);
+
+		let mut eqn = equation(&[(bo2.clone(), -3), (al2bo3.clone(), 2), (al.clone(), -4)]);
+		assert_eq!(eqn.to_string(), "4Al + 3Bo2 -> 2Al2Bo3");
+		// equal equations display the same, whatever order their species were added in
+		let reordered = equation(&[(al, -4), (al2bo3, 2), (bo2, -3)]);
+		assert_eq!(reordered, eqn);
+		assert_eq!(reordered.to_string(), eqn.to_string());
+		eqn.set_reversible(true);
+		assert_eq!(eqn.to_string(), "4Al + 3Bo2 <=> 2Al2Bo3");
+	}
+
+	#[test]
+	fn conserves_charge() {
+		let p_table = PeriodicTable::new_alphabetic();
+		let ion = |parts: &[(&str, u32)], charge: i32| {
+			let mut result = formula(&p_table, parts);
+			result.set_charge(charge);
+			result
+		};
+		let al = ion(&[("Al", 1)], 0);
+		let ch_2plus = ion(&[("Ch", 1)], 2);
+		let al_3plus = ion(&[("Al", 1)], 3);
+		let ch = ion(&[("Ch", 1)], 0);
+
+		let unbalanced = equation(&[
+			(al.clone(), -1),
+			(ch_2plus.clone(), -1),
+			(al_3plus.clone(), 1),
+			(ch.clone(), 1),
+		]);
+		let balanced = equation(&[(al, -2), (ch_2plus, -3), (al_3plus.clone(), 2), (ch, 3)]);
+		assert_eq!(unbalanced.balance(), Ok(balanced.clone()));
+		assert_eq!(balanced.to_string(), "2Al + 3Ch²⁺ -> 2Al³⁺ + 3Ch");
+
+		// the atoms balance but the charges can't
+		let no_solution = equation(&[(ion(&[("Al", 1)], 1), -1), (al_3plus, 1)]);
+		assert_eq!(no_solution.balance(), Err(BalanceError::NoSolution));
 	}
 }
@@ -1,19 +1,28 @@
 use super::{
-	real_number::RealNumber,
-	elements::{PeriodicTable, Element},
-	formulas::MolecularFormula,
 	chem_eqn::ChemEqn,
+	elements::{Element, PeriodicTable, ELECTRON},
+	formulas::MolecularFormula,
+	methods::MethodRegistry,
+	quantity::Quantity,
+	real_number::RealNumber,
+};
+use std::{
+	borrow::Cow,
+	collections::hash_map::HashMap,
+	error::Error,
+	fmt::{Debug, Display},
 };
-use std::{collections::hash_map::HashMap, error::Error, fmt::{Display, Debug}};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum DataType {
 	String,
 	Integer,
 	RealNumber,
+	Quantity,
 	ElementRef,
 	MolecularFormula,
 	ChemEqn,
+	Tuple,
 }
 impl Display for DataType {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -21,9 +30,11 @@ impl Display for DataType {
 			DataType::String => write!(f, "string"),
 			DataType::Integer => write!(f, "integer"),
 			DataType::RealNumber => write!(f, "real number"),
+			DataType::Quantity => write!(f, "quantity"),
 			DataType::ElementRef => write!(f, "element reference"),
 			DataType::MolecularFormula => write!(f, "molecular formula"),
 			DataType::ChemEqn => write!(f, "chemical equation"),
+			DataType::Tuple => write!(f, "tuple"),
 		}
 	}
 }
@@ -33,9 +44,11 @@ impl From<&Value<'_>> for DataType {
 			Value::String(_) => DataType::String,
 			Value::Integer(_) => DataType::Integer,
 			Value::RealNumber(_) => DataType::RealNumber,
+			Value::Quantity(_) => DataType::Quantity,
 			Value::ElementRef(_) => DataType::ElementRef,
 			Value::MolecularFormula(_) => DataType::MolecularFormula,
 			Value::ChemEqn(_) => DataType::ChemEqn,
+			Value::Tuple(_) => DataType::Tuple,
 		}
 	}
 }
@@ -45,28 +58,50 @@ pub enum Value<'a> {
 	String(String),
 	Integer(u32),
 	RealNumber(RealNumber),
+	Quantity(Quantity),
 	ElementRef(&'a Element),
 	MolecularFormula(MolecularFormula<'a>),
 	ChemEqn(ChemEqn<'a>),
+	Tuple(Vec<Value<'a>>),
 }
 impl<'a> Value<'a> {
 	pub fn as_type<T: ExpectFromValue<'a>>(self) -> Result<T, BadTypeError<'a>> {
 		ExpectFromValue::expect_from_value(self)
 	}
-}
-impl Display for Value<'_> {
-	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		write!(f, "({}) ", DataType::from(self))?;
+
+	/// Writes the value without its type, so that the members of a tuple aren't each prefixed
+	fn fmt_bare(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 		match self {
 			Value::String(string) => Display::fmt(string, f),
 			Value::Integer(integer) => Display::fmt(integer, f),
 			Value::RealNumber(real) => Display::fmt(real, f),
+			Value::Quantity(quantity) => Display::fmt(quantity, f),
 			Value::ElementRef(element) => Display::fmt(element, f),
 			Value::MolecularFormula(formula) => Display::fmt(formula, f),
 			Value::ChemEqn(eqn) => Display::fmt(eqn, f),
+			Value::Tuple(values) => {
+				write!(f, "(")?;
+				for (i, value) in values.iter().enumerate() {
+					if i > 0 {
+						write!(f, ", ")?;
+					}
+					value.fmt_bare(f)?;
+				}
+				// distinguish a 1-tuple from a parenthesized value
+				if values.len() == 1 {
+					write!(f, ",")?;
+				}
+				write!(f, ")")
+			},
 		}
 	}
 }
+impl Display for Value<'_> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "({}) ", DataType::from(self))?;
+		self.fmt_bare(f)
+	}
+}
 impl From<String> for Value<'_> {
 	fn from(value: String) -> Self {
 		Value::String(value)
@@ -82,6 +117,11 @@ impl From<RealNumber> for Value<'_> {
 		Value::RealNumber(value)
 	}
 }
+impl From<Quantity> for Value<'_> {
+	fn from(value: Quantity) -> Self {
+		Value::Quantity(value)
+	}
+}
 impl<'a> From<&'a Element> for Value<'a> {
 	fn from(value: &'a Element) -> Self {
 		Value::ElementRef(value)
@@ -97,8 +137,13 @@ impl<'a> From<ChemEqn<'a>> for Value<'a> {
 		Value::ChemEqn(value)
 	}
 }
+impl<'a> From<Vec<Value<'a>>> for Value<'a> {
+	fn from(value: Vec<Value<'a>>) -> Self {
+		Value::Tuple(value)
+	}
+}
 
-trait ExpectFromValue<'a>: Sized {
+pub trait ExpectFromValue<'a>: Sized {
 	fn expect_from_value(value: Value<'a>) -> Result<Self, BadTypeError<'a>>;
 }
 impl<'a> ExpectFromValue<'a> for String {
@@ -106,7 +151,10 @@ impl<'a> ExpectFromValue<'a> for String {
 		if let Value::String(string) = value {
 			Ok(string)
 		} else {
-			Err(BadTypeError { expected_type: DataType::String, found_value: value })
+			Err(BadTypeError::Unexpected {
+				expected_type: DataType::String,
+				found_value: value,
+			})
 		}
 	}
 }
@@ -115,7 +163,10 @@ impl<'a> ExpectFromValue<'a> for u32 {
 		if let Value::Integer(integer) = value {
 			Ok(integer)
 		} else {
-			Err(BadTypeError { expected_type: DataType::Integer, found_value: value })
+			Err(BadTypeError::Unexpected {
+				expected_type: DataType::Integer,
+				found_value: value,
+			})
 		}
 	}
 }
@@ -124,7 +175,22 @@ impl<'a> ExpectFromValue<'a> for RealNumber {
 		if let Value::RealNumber(real) = value {
 			Ok(real)
 		} else {
-			Err(BadTypeError { expected_type: DataType::RealNumber, found_value: value })
+			Err(BadTypeError::Unexpected {
+				expected_type: DataType::RealNumber,
+				found_value: value,
+			})
+		}
+	}
+}
+impl<'a> ExpectFromValue<'a> for Quantity {
+	fn expect_from_value(value: Value<'a>) -> Result<Self, BadTypeError<'a>> {
+		if let Value::Quantity(quantity) = value {
+			Ok(quantity)
+		} else {
+			Err(BadTypeError::Unexpected {
+				expected_type: DataType::Quantity,
+				found_value: value,
+			})
 		}
 	}
 }
@@ -133,7 +199,10 @@ impl<'a> ExpectFromValue<'a> for &'a Element {
 		if let Value::ElementRef(element) = value {
 			Ok(element)
 		} else {
-			Err(BadTypeError { expected_type: DataType::ElementRef, found_value: value })
+			Err(BadTypeError::Unexpected {
+				expected_type: DataType::ElementRef,
+				found_value: value,
+			})
 		}
 	}
 }
@@ -142,7 +211,10 @@ impl<'a> ExpectFromValue<'a> for MolecularFormula<'a> {
 		if let Value::MolecularFormula(formula) = value {
 			Ok(formula)
 		} else {
-			Err(BadTypeError { expected_type: DataType::MolecularFormula, found_value: value })
+			Err(BadTypeError::Unexpected {
+				expected_type: DataType::MolecularFormula,
+				found_value: value,
+			})
 		}
 	}
 }
@@ -151,7 +223,22 @@ impl<'a> ExpectFromValue<'a> for ChemEqn<'a> {
 		if let Value::ChemEqn(eqn) = value {
 			Ok(eqn)
 		} else {
-			Err(BadTypeError { expected_type: DataType::ChemEqn, found_value: value })
+			Err(BadTypeError::Unexpected {
+				expected_type: DataType::ChemEqn,
+				found_value: value,
+			})
+		}
+	}
+}
+impl<'a> ExpectFromValue<'a> for Vec<Value<'a>> {
+	fn expect_from_value(value: Value<'a>) -> Result<Self, BadTypeError<'a>> {
+		if let Value::Tuple(values) = value {
+			Ok(values)
+		} else {
+			Err(BadTypeError::Unexpected {
+				expected_type: DataType::Tuple,
+				found_value: value,
+			})
 		}
 	}
 }
@@ -159,7 +246,7 @@ impl<'a> ExpectFromValue<'a> for ChemEqn<'a> {
 #[derive(Debug)]
 pub enum DictAccessError<'a> {
 	BadType(BadTypeError<'a>),
-	UndefinedId(UndefinedIdentifierError<'a>),
+	UndefinedId(UndefinedIdentifierError),
 }
 impl Display for DictAccessError<'_> {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -173,20 +260,52 @@ impl Display for DictAccessError<'_> {
 impl Error for DictAccessError<'_> {
 	fn source(&self) -> Option<&(dyn Error + 'static)> {
 		match self {
-			DictAccessError::BadType(err) => Some(err),
+			// a BadTypeError borrows the value it complains about, so it can't be a 'static source
+			DictAccessError::BadType(_) => None,
 			DictAccessError::UndefinedId(err) => Some(err),
 		}
 	}
 }
 
 #[derive(Debug)]
-pub struct BadTypeError<'a> {
-	expected_type: DataType,
-	found_value: Value<'a>,
+pub enum BadTypeError<'a> {
+	/// A value was not of the type its context requires
+	Unexpected {
+		expected_type: DataType,
+		found_value: Value<'a>,
+	},
+	/// An operator does not apply to operands of these types
+	Operands {
+		operator: &'static str,
+		lhs_type: DataType,
+		rhs_type: DataType,
+	},
 }
 impl Display for BadTypeError<'_> {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		write!(f, "Expected type {}, found value {}", self.expected_type, self.found_value)
+		match self {
+			BadTypeError::Unexpected {
+				expected_type,
+				found_value,
+			} => {
+				write!(
+					f,
+					"Expected type {}, found value {}",
+					expected_type, found_value
+				)
+			},
+			BadTypeError::Operands {
+				operator,
+				lhs_type,
+				rhs_type,
+			} => {
+				write!(
+					f,
+					"Cannot apply '{}' to a {} and a {}",
+					operator, lhs_type, rhs_type
+				)
+			},
+		}
 	}
 }
 impl Error for BadTypeError<'_> {}
@@ -197,17 +316,17 @@ impl<'a> From<BadTypeError<'a>> for DictAccessError<'a> {
 }
 
 #[derive(Debug)]
-pub struct UndefinedIdentifierError<'a> {
-	name: &'a str,
+pub struct UndefinedIdentifierError {
+	name: String,
 }
-impl Display for UndefinedIdentifierError<'_> {
+impl Display for UndefinedIdentifierError {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 		write!(f, "Undefined identifier '{}'", self.name)
 	}
 }
-impl Error for UndefinedIdentifierError<'_> {}
-impl From<UndefinedIdentifierError<'_>> for DictAccessError<'_> {
-	fn from(value: UndefinedIdentifierError<'_>) -> Self {
+impl Error for UndefinedIdentifierError {}
+impl From<UndefinedIdentifierError> for DictAccessError<'_> {
+	fn from(value: UndefinedIdentifierError) -> Self {
 		DictAccessError::UndefinedId(value)
 	}
 }
@@ -215,16 +334,35 @@ impl From<UndefinedIdentifierError<'_>> for DictAccessError<'_> {
 #[derive(Debug)]
 pub struct Dictionary<'a> {
 	values: HashMap<String, Value<'a>>,
+	// element symbols are looked up in the table rather than copied into values
+	p_table: Option<&'a PeriodicTable>,
+	methods: MethodRegistry,
+}
+
+impl Default for Dictionary<'_> {
+	fn default() -> Self {
+		Self::new()
+	}
 }
 
 impl<'a> Dictionary<'a> {
 	pub fn new() -> Self {
 		Dictionary {
 			values: HashMap::new(),
+			p_table: None,
+			methods: MethodRegistry::with_builtins(),
 		}
 	}
 
-	pub fn clear_value(&mut self, name: &str) -> Option<Value> {
+	pub fn methods(&self) -> &MethodRegistry {
+		&self.methods
+	}
+
+	pub fn methods_mut(&mut self) -> &mut MethodRegistry {
+		&mut self.methods
+	}
+
+	pub fn clear_value(&mut self, name: &str) -> Option<Value<'a>> {
 		self.values.remove(name)
 	}
 
@@ -232,14 +370,37 @@ impl<'a> Dictionary<'a> {
 		self.values.insert(name.to_owned(), value.into());
 	}
 
-	pub fn get_value<'b>(&self, name: &'b str) -> Result<&Value<'a>, UndefinedIdentifierError<'b>> {
-		self.values.get(name).ok_or(UndefinedIdentifierError { name })
+	/// Returns the value assigned to the name or else the element with that symbol (including `e`
+	/// for the electron), if elements have been loaded
+	pub fn get_value(&self, name: &str) -> Result<Cow<'_, Value<'a>>, UndefinedIdentifierError> {
+		if let Some(value) = self.values.get(name) {
+			return Ok(Cow::Borrowed(value));
+		}
+		self.get_element(name)
+			.map(|element| Cow::Owned(Value::ElementRef(element)))
+			.ok_or_else(|| UndefinedIdentifierError {
+				name: name.to_owned(),
+			})
 	}
 
-	pub fn load_elements(&mut self, p_table: &'a PeriodicTable) {
-		for element_info in p_table {
-			self.values
-				.insert(element_info.symbol.clone(), Value::ElementRef(element_info));
+	/// Returns the element with the symbol from the loaded periodic table
+	pub fn get_element(&self, symbol: &str) -> Option<&'a Element> {
+		let p_table = self.p_table?;
+		if symbol == ELECTRON.symbol {
+			return Some(&ELECTRON);
 		}
+		p_table.get_element(symbol)
+	}
+
+	/// Returns the isotope of the element from the loaded periodic table
+	pub fn get_isotope(&self, element: &Element, mass_number: u32) -> Option<&'a Element> {
+		let atomic_number = u32::try_from(element.atomic_number).ok()?;
+		self.p_table?.get_isotope(atomic_number, mass_number)
+	}
+
+	/// Makes the elements of the periodic table available by their symbols, replacing any
+	/// previously loaded table. Values assigned to the same names take precedence.
+	pub fn load_elements(&mut self, p_table: &'a PeriodicTable) {
+		self.p_table = Some(p_table);
 	}
 }
@@ -1,46 +1,165 @@
-use std::fmt;
+use super::real_number::{ParseRealNumberError, RealNumber};
+use crate::helper::superscript::to_superscript;
+use std::{
+	collections::HashMap,
+	error::Error,
+	fmt,
+	hash::{Hash, Hasher},
+	str::FromStr,
+	sync::LazyLock,
+};
 
-#[derive(Debug)]
+/// The data for PeriodicTable::standard, in the format described by PeriodicTable::load
+static STANDARD_TABLE: &str = include_str!("standard_ptable.txt");
+
+#[derive(Debug, Default, Clone)]
 pub struct PeriodicTable {
 	elements: Vec<Element>,
+	// indices into elements, so that lookups don't need to scan the whole table
+	by_symbol: HashMap<String, usize>,
+	by_number: HashMap<i32, usize>,
+	// keyed by the lowercase name
+	by_name: HashMap<String, usize>,
+	isotopes: Vec<Element>,
+	// indices into isotopes, by atomic number and mass number
+	by_isotope: HashMap<(i32, u32), usize>,
+	// only for isotopes with their own symbols, e.g. D
+	by_isotope_symbol: HashMap<String, usize>,
 }
 
 impl PeriodicTable {
-	pub fn from(string: String) -> Self {
-		PeriodicTable {
-			elements: string
-				.lines()
-				.map(|line| {
-					let mut fields = line.split_ascii_whitespace();
-					let atomic_number = fields
-						.next()
-						.expect(
-							"There should've been an atomic number as the first item of the line",
-						)
-						.parse()
-						.expect("Should've been able to parse the atomic number as an integer");
-					let symbol = fields
-						.next()
-						.expect(
-							"There should've been an atomic symbol as the second item of the line",
-						)
-						.to_string();
-					let name = fields
-						.next()
-						.expect(
-							"There should've been an element name as the third item of the line",
-						)
-						.to_string();
-					Element {
-						atomic_number,
-						symbol,
-						name,
-					}
+	/// Loads a periodic table with one element per line. Each line has the atomic number, symbol,
+	/// and name, optionally followed by (in order) the standard atomic weight, Pauling
+	/// electronegativity, group, period, block, common oxidation states, and electron
+	/// configuration, e.g.
+	///
+	/// `6 C carbon 12.011 2.55 14 2 p -4,+4 [He] 2s2 2p2`
+	///
+	/// A `-` marks a property as unknown. Blank lines and lines starting with `#` are ignored.
+	///
+	/// Isotopes are on lines starting with the atomic number and mass number, followed by the
+	/// exact isotopic mass and natural abundance (as a fraction), and optionally a symbol and name
+	/// for isotopes that have their own, e.g.
+	///
+	/// `1-2 2.01410177812 0.000115 D deuterium`
+	///
+	/// An isotope must come after its element. No two elements (or isotopes) may share a symbol or
+	/// an atomic number (and mass number), and no two elements may share a name.
+	pub fn load(string: &str) -> Result<Self, LoadTableError> {
+		let mut p_table = PeriodicTable::default();
+		// the line on which each symbol, atomic number, name, and isotope was first defined
+		let mut symbol_lines: HashMap<String, usize> = HashMap::new();
+		let mut number_lines: HashMap<i32, usize> = HashMap::new();
+		let mut name_lines: HashMap<String, usize> = HashMap::new();
+		let mut isotope_lines: HashMap<(i32, u32), usize> = HashMap::new();
+		for (i, line) in string.lines().enumerate() {
+			let line_number = i + 1;
+			if line.trim().is_empty() || line.trim_start().starts_with('#') {
+				continue;
+			}
+
+			// points the error at the repeated field, given by its index among the line's fields
+			let duplicate = |field_index, kind| {
+				let (column, _) = Fields::new(line, line_number)
+					.nth(field_index)
+					.expect("The repeated field should've been parsed");
+				Err(LoadTableError {
+					line: line_number,
+					column,
+					kind,
 				})
-				.collect(),
+			};
+			let is_isotope = line
+				.split_ascii_whitespace()
+				.next()
+				.is_some_and(|first_field| first_field.contains('-'));
+			if is_isotope {
+				let isotope = parse_isotope(line, line_number, &p_table)?;
+				let mass_number = isotope.mass_number.expect("Should've been an isotope");
+				let key = (isotope.atomic_number, mass_number);
+				if let Some(&first_line) = isotope_lines.get(&key) {
+					return duplicate(
+						0,
+						LoadTableErrorKind::DuplicateIsotope {
+							atomic_number: isotope.atomic_number,
+							mass_number,
+							first_line,
+						},
+					);
+				}
+				if isotope.has_own_symbol() {
+					if let Some(&first_line) = symbol_lines.get(&isotope.symbol) {
+						return duplicate(
+							3,
+							LoadTableErrorKind::DuplicateSymbol {
+								symbol: isotope.symbol,
+								first_line,
+							},
+						);
+					}
+					symbol_lines.insert(isotope.symbol.clone(), line_number);
+				}
+				isotope_lines.insert(key, line_number);
+				p_table
+					.add_isotope(isotope)
+					.expect("Should've parsed a mass number");
+				continue;
+			}
+
+			let element = parse_element(line, line_number)?;
+			if let Some(&first_line) = symbol_lines.get(&element.symbol) {
+				return duplicate(
+					1,
+					LoadTableErrorKind::DuplicateSymbol {
+						symbol: element.symbol,
+						first_line,
+					},
+				);
+			}
+			if let Some(&first_line) = number_lines.get(&element.atomic_number) {
+				return duplicate(
+					0,
+					LoadTableErrorKind::DuplicateAtomicNumber {
+						atomic_number: element.atomic_number,
+						first_line,
+					},
+				);
+			}
+			let name = element.name.to_lowercase();
+			if let Some(&first_line) = name_lines.get(&name) {
+				return duplicate(
+					2,
+					LoadTableErrorKind::DuplicateName {
+						name: element.name,
+						first_line,
+					},
+				);
+			}
+			symbol_lines.insert(element.symbol.clone(), line_number);
+			number_lines.insert(element.atomic_number, line_number);
+			name_lines.insert(name, line_number);
+			p_table
+				.add_element(element)
+				.expect("Should've checked for conflicts");
 		}
+		Ok(p_table)
+	}
+
+	/// Like `load`, but panics if the string is malformed. Meant for tables known to be valid.
+	pub fn from(string: String) -> Self {
+		match Self::load(&string) {
+			Ok(p_table) => p_table,
+			Err(err) => panic!("Should've been a valid periodic table: {}", err),
+		}
+	}
+
+	/// Creates the periodic table of all 118 known elements, which is embedded in the library so
+	/// that it doesn't depend on any data files
+	pub fn standard() -> Self {
+		PeriodicTable::from(STANDARD_TABLE.to_string())
 	}
 
+	/// Creates a periodic table of made-up elements, which is convenient for testing
 	pub fn new_alphabetic() -> Self {
 		PeriodicTable::from(
 			"\
@@ -63,15 +182,519 @@ impl PeriodicTable {
 		)
 	}
 
-	pub fn add_element(&mut self, element: Element) {
+	/// Adds the element, unless another element already has its atomic number, symbol, or name
+	pub fn add_element(&mut self, element: Element) -> Result<(), ElementConflictError> {
+		if self.by_number.contains_key(&element.atomic_number) {
+			return Err(ElementConflictError::AtomicNumber(element.atomic_number));
+		}
+		self.check_conflicts(&element, None)?;
 		self.elements.push(element);
+		self.index(self.elements.len() - 1);
+		Ok(())
+	}
+
+	/// Adds the elements of the other table to this one, e.g. to load a user's table on top of the
+	/// standard one. An element with the same atomic number as an existing one updates it; the
+	/// properties the other table leaves unknown stay as they were. Isotopes replace any existing
+	/// isotope with the same atomic number and mass number. If an element would end up with the
+	/// same symbol or name as another, this table is left unchanged.
+	pub fn overlay(&mut self, other: PeriodicTable) -> Result<(), ElementConflictError> {
+		let mut result = self.clone();
+		for isotope in other.isotopes {
+			result.add_isotope(isotope)?;
+		}
+		for element in other.elements {
+			match result.by_number.get(&element.atomic_number) {
+				Some(&i) => {
+					result.check_conflicts(&element, Some(i))?;
+					// the symbol and name may change, so they need to be reindexed
+					result.unindex(i);
+					result.elements[i].update_from(element);
+					result.index(i);
+				},
+				None => result.add_element(element)?,
+			}
+		}
+		*self = result;
+		Ok(())
+	}
+
+	/// Checks that no element other than the one at index `replacing` has the element's symbol or
+	/// name, since the lookups by symbol and name could only find one of them
+	fn check_conflicts(
+		&self,
+		element: &Element,
+		replacing: Option<usize>,
+	) -> Result<(), ElementConflictError> {
+		let taken_by = |index: Option<&usize>| {
+			index
+				.filter(|&&i| Some(i) != replacing)
+				.map(|&i| self.elements[i].atomic_number)
+		};
+		if let Some(atomic_number) = taken_by(self.by_symbol.get(&element.symbol)) {
+			return Err(ElementConflictError::Symbol {
+				symbol: element.symbol.clone(),
+				atomic_number,
+			});
+		}
+		if let Some(atomic_number) = taken_by(self.by_name.get(&element.name.to_lowercase())) {
+			return Err(ElementConflictError::Name {
+				name: element.name.clone(),
+				atomic_number,
+			});
+		}
+		Ok(())
+	}
+
+	/// Adds an isotope (an Element with a mass number), replacing any existing isotope with the
+	/// same atomic number and mass number. Returns an error if the element has no mass number.
+	pub fn add_isotope(&mut self, isotope: Element) -> Result<(), ElementConflictError> {
+		let mass_number = match isotope.mass_number {
+			Some(mass_number) => mass_number,
+			None => {
+				return Err(ElementConflictError::NotAnIsotope {
+					symbol: isotope.symbol,
+				})
+			},
+		};
+		let key = (isotope.atomic_number, mass_number);
+		let i = match self.by_isotope.get(&key) {
+			Some(&i) => {
+				let old = &self.isotopes[i];
+				if old.has_own_symbol() {
+					self.by_isotope_symbol.remove(&old.symbol);
+				}
+				self.isotopes[i] = isotope;
+				i
+			},
+			None => {
+				self.isotopes.push(isotope);
+				self.isotopes.len() - 1
+			},
+		};
+		self.by_isotope.insert(key, i);
+		if self.isotopes[i].has_own_symbol() {
+			self.by_isotope_symbol
+				.insert(self.isotopes[i].symbol.clone(), i);
+		}
+		Ok(())
 	}
 
+	fn index(&mut self, i: usize) {
+		let element = &self.elements[i];
+		self.by_symbol.insert(element.symbol.clone(), i);
+		self.by_number.insert(element.atomic_number, i);
+		self.by_name.insert(element.name.to_lowercase(), i);
+	}
+
+	fn unindex(&mut self, i: usize) {
+		let element = &self.elements[i];
+		self.by_symbol.remove(&element.symbol);
+		self.by_number.remove(&element.atomic_number);
+		self.by_name.remove(&element.name.to_lowercase());
+	}
+
+	/// Looks up an element by its symbol, including isotopes with their own symbols, e.g. D
 	pub fn get_element(&self, symbol: &str) -> Option<&Element> {
-		self.elements.iter().find(|&e| e.symbol == symbol)
+		match self.by_symbol.get(symbol) {
+			Some(&i) => Some(&self.elements[i]),
+			None => self
+				.by_isotope_symbol
+				.get(symbol)
+				.map(|&i| &self.isotopes[i]),
+		}
+	}
+
+	pub fn get_isotope(&self, atomic_number: u32, mass_number: u32) -> Option<&Element> {
+		let atomic_number = i32::try_from(atomic_number).ok()?;
+		self.by_isotope
+			.get(&(atomic_number, mass_number))
+			.map(|&i| &self.isotopes[i])
+	}
+
+	/// Iterates over the isotopes of the element with the atomic number, in the order they were
+	/// added
+	pub fn isotopes_of(&self, atomic_number: u32) -> impl Iterator<Item = &Element> {
+		self.isotopes.iter().filter(move |isotope| {
+			u32::try_from(isotope.atomic_number).is_ok_and(|number| number == atomic_number)
+		})
+	}
+
+	pub fn get_by_number(&self, atomic_number: u32) -> Option<&Element> {
+		let atomic_number = i32::try_from(atomic_number).ok()?;
+		self.by_number
+			.get(&atomic_number)
+			.map(|&i| &self.elements[i])
+	}
+
+	/// Looks up an element by its name, ignoring case
+	pub fn get_by_name(&self, name: &str) -> Option<&Element> {
+		self.by_name
+			.get(&name.to_lowercase())
+			.map(|&i| &self.elements[i])
+	}
+}
+
+impl FromStr for PeriodicTable {
+	type Err = LoadTableError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Self::load(s)
+	}
+}
+
+impl TryFrom<&str> for PeriodicTable {
+	type Error = LoadTableError;
+
+	fn try_from(value: &str) -> Result<Self, Self::Error> {
+		Self::load(value)
+	}
+}
+
+impl TryFrom<String> for PeriodicTable {
+	type Error = LoadTableError;
+
+	fn try_from(value: String) -> Result<Self, Self::Error> {
+		Self::load(&value)
+	}
+}
+
+/// The whitespace-separated fields of a line, along with the 1-based column of each
+struct Fields<'s> {
+	line: &'s str,
+	line_number: usize,
+	rest: std::iter::Peekable<std::str::CharIndices<'s>>,
+}
+
+impl<'s> Fields<'s> {
+	fn new(line: &'s str, line_number: usize) -> Self {
+		Self {
+			line,
+			line_number,
+			rest: line.char_indices().peekable(),
+		}
+	}
+
+	fn column_of(&self, byte_index: usize) -> usize {
+		self.line[..byte_index].chars().count() + 1
+	}
+
+	fn error(&self, column: usize, kind: LoadTableErrorKind) -> LoadTableError {
+		LoadTableError {
+			line: self.line_number,
+			column,
+			kind,
+		}
+	}
+
+	/// Parses the next field, which must be present
+	fn expect_next<T: FromStr>(&mut self, expected: &'static str) -> Result<T, LoadTableError> {
+		match self.next() {
+			None => Err(self.error(
+				self.column_of(self.line.len()),
+				LoadTableErrorKind::MissingField { expected },
+			)),
+			Some((column, field)) => self.parse(column, field, expected),
+		}
+	}
+
+	/// Parses the next field if it is present and not `-`
+	fn next_known<T: FromStr>(
+		&mut self,
+		expected: &'static str,
+	) -> Result<Option<T>, LoadTableError> {
+		match self.next() {
+			None => Ok(None),
+			Some((_, "-")) => Ok(None),
+			Some((column, field)) => self.parse(column, field, expected).map(Some),
+		}
+	}
+
+	fn parse<T: FromStr>(
+		&self,
+		column: usize,
+		field: &str,
+		expected: &'static str,
+	) -> Result<T, LoadTableError> {
+		field.parse().map_err(|_| {
+			self.error(
+				column,
+				LoadTableErrorKind::MalformedField {
+					expected,
+					found: field.to_string(),
+				},
+			)
+		})
+	}
+}
+
+impl<'s> Iterator for Fields<'s> {
+	type Item = (usize, &'s str);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		while self.rest.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+		let &(start, _) = self.rest.peek()?;
+		let mut end = self.line.len();
+		while let Some(&(i, c)) = self.rest.peek() {
+			if c.is_whitespace() {
+				end = i;
+				break;
+			}
+			self.rest.next();
+		}
+		Some((self.column_of(start), &self.line[start..end]))
+	}
+}
+
+fn parse_element(line: &str, line_number: usize) -> Result<Element, LoadTableError> {
+	const ATOMIC_NUMBER: &str = "a positive integer atomic number";
+
+	let mut fields = Fields::new(line, line_number);
+	let (column, field) = fields.next().expect("Blank lines should've been skipped");
+	let atomic_number: i32 = fields.parse(column, field, ATOMIC_NUMBER)?;
+	if atomic_number < 1 {
+		return Err(fields.error(
+			column,
+			LoadTableErrorKind::MalformedField {
+				expected: ATOMIC_NUMBER,
+				found: field.to_string(),
+			},
+		));
+	}
+	let symbol: String = fields.expect_next("an element symbol")?;
+	let name: String = fields.expect_next("an element name")?;
+	let mut element = Element::new(atomic_number, &symbol, &name);
+
+	// the remaining columns are optional, and "-" means that the property is unknown
+	element.atomic_weight =
+		fields.next_known("an atomic weight such as 12.011, 4.002602(2), or [98]")?;
+	element.electronegativity = fields.next_known("an electronegativity")?;
+	element.group = fields.next_known("a group from 1 to 18")?;
+	element.period = fields.next_known("a period")?;
+	element.block = fields.next_known("a block (s, p, d, or f)")?;
+	element.oxidation_states = fields
+		.next_known::<OxidationStates>("comma-separated oxidation states such as -4,+4")?
+		.map(|states| states.0)
+		.unwrap_or_default();
+	// the electron configuration contains spaces, so it takes up the rest of the line
+	let configuration: Vec<&str> = fields.map(|(_, field)| field).collect();
+	if !configuration.is_empty() && configuration != ["-"] {
+		element.electron_configuration = Some(configuration.join(" "));
+	}
+	Ok(element)
+}
+
+fn parse_isotope(
+	line: &str,
+	line_number: usize,
+	p_table: &PeriodicTable,
+) -> Result<Element, LoadTableError> {
+	let mut fields = Fields::new(line, line_number);
+	let (column, field) = fields.next().expect("Blank lines should've been skipped");
+	let IsotopeNumbers(atomic_number, mass_number) = fields.parse(
+		column,
+		field,
+		"an atomic number and mass number such as 6-13",
+	)?;
+	let Some(element) = u32::try_from(atomic_number)
+		.ok()
+		.and_then(|atomic_number| p_table.get_by_number(atomic_number))
+	else {
+		return Err(fields.error(column, LoadTableErrorKind::UnknownElement { atomic_number }));
+	};
+
+	let atomic_weight = fields.expect_next("an isotopic mass such as 13.00335483507")?;
+	let abundance = fields.next_known("an abundance between 0 and 1")?;
+	let symbol = fields.next().map_or_else(
+		|| format!("{}{}", to_superscript(mass_number), element.symbol),
+		|(_, symbol)| symbol.to_string(),
+	);
+	let name = fields.next().map_or_else(
+		|| format!("{}-{}", element.name, mass_number),
+		|(_, name)| name.to_string(),
+	);
+	Ok(Element {
+		symbol,
+		name,
+		mass_number: Some(mass_number),
+		atomic_weight: Some(atomic_weight),
+		abundance,
+		..element.clone()
+	})
+}
+
+struct IsotopeNumbers(i32, u32);
+
+impl FromStr for IsotopeNumbers {
+	type Err = ();
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (atomic_number, mass_number) = s.split_once('-').ok_or(())?;
+		Ok(IsotopeNumbers(
+			atomic_number.parse().map_err(|_| ())?,
+			mass_number.parse().map_err(|_| ())?,
+		))
 	}
 }
 
+struct OxidationStates(Vec<i32>);
+
+impl FromStr for OxidationStates {
+	type Err = std::num::ParseIntError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		s.split(',')
+			.map(str::parse)
+			.collect::<Result<_, _>>()
+			.map(OxidationStates)
+	}
+}
+
+#[derive(Debug, PartialEq)]
+pub struct LoadTableError {
+	/// Starting from 1
+	pub line: usize,
+	/// Starting from 1, in characters; the column of the offending field, or of the end of the line
+	/// if a field is missing
+	pub column: usize,
+	pub kind: LoadTableErrorKind,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum LoadTableErrorKind {
+	MissingField {
+		expected: &'static str,
+	},
+	MalformedField {
+		expected: &'static str,
+		found: String,
+	},
+	DuplicateSymbol {
+		symbol: String,
+		first_line: usize,
+	},
+	DuplicateAtomicNumber {
+		atomic_number: i32,
+		first_line: usize,
+	},
+	/// Names are compared ignoring case
+	DuplicateName {
+		name: String,
+		first_line: usize,
+	},
+	DuplicateIsotope {
+		atomic_number: i32,
+		mass_number: u32,
+		first_line: usize,
+	},
+	/// An isotope's element wasn't defined on an earlier line
+	UnknownElement {
+		atomic_number: i32,
+	},
+}
+
+impl fmt::Display for LoadTableError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "Line {}, column {}: ", self.line, self.column)?;
+		match &self.kind {
+			LoadTableErrorKind::MissingField { expected } => {
+				write!(f, "expected {}, found the end of the line", expected)
+			},
+			LoadTableErrorKind::MalformedField { expected, found } => {
+				write!(f, "expected {}, found '{}'", expected, found)
+			},
+			LoadTableErrorKind::DuplicateSymbol { symbol, first_line } => write!(
+				f,
+				"the symbol '{}' was already defined on line {}",
+				symbol, first_line
+			),
+			LoadTableErrorKind::DuplicateAtomicNumber {
+				atomic_number,
+				first_line,
+			} => write!(
+				f,
+				"atomic number {} was already defined on line {}",
+				atomic_number, first_line
+			),
+			LoadTableErrorKind::DuplicateName { name, first_line } => write!(
+				f,
+				"the name '{}' was already defined on line {}",
+				name, first_line
+			),
+			LoadTableErrorKind::DuplicateIsotope {
+				atomic_number,
+				mass_number,
+				first_line,
+			} => write!(
+				f,
+				"the isotope {}-{} was already defined on line {}",
+				atomic_number, mass_number, first_line
+			),
+			LoadTableErrorKind::UnknownElement { atomic_number } => write!(
+				f,
+				"no element with atomic number {} was defined before its isotopes",
+				atomic_number
+			),
+		}
+	}
+}
+
+impl Error for LoadTableError {}
+
+/// An element can't be added to a periodic table because another element already has one of its
+/// identifying properties
+#[derive(Debug, PartialEq)]
+pub enum ElementConflictError {
+	AtomicNumber(i32),
+	/// The element with the atomic number already has the symbol
+	Symbol {
+		symbol: String,
+		atomic_number: i32,
+	},
+	/// The element with the atomic number already has the name, ignoring case
+	Name {
+		name: String,
+		atomic_number: i32,
+	},
+	/// The element was added as an isotope but has no mass number
+	NotAnIsotope {
+		symbol: String,
+	},
+}
+
+impl fmt::Display for ElementConflictError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			ElementConflictError::AtomicNumber(atomic_number) => write!(
+				f,
+				"an element with atomic number {} already exists",
+				atomic_number
+			),
+			ElementConflictError::Symbol {
+				symbol,
+				atomic_number,
+			} => write!(
+				f,
+				"the symbol '{}' already belongs to atomic number {}",
+				symbol, atomic_number
+			),
+			ElementConflictError::Name {
+				name,
+				atomic_number,
+			} => write!(
+				f,
+				"the name '{}' already belongs to atomic number {}",
+				name, atomic_number
+			),
+			ElementConflictError::NotAnIsotope { symbol } => {
+				write!(f, "'{}' has no mass number, so it isn't an isotope", symbol)
+			},
+		}
+	}
+}
+
+impl Error for ElementConflictError {}
+
 impl<'a> IntoIterator for &'a PeriodicTable {
 	type Item = &'a Element;
 	type IntoIter = std::slice::Iter<'a, Element>;
@@ -90,11 +713,177 @@ impl fmt::Display for PeriodicTable {
 	}
 }
 
-#[derive(Debug, Eq, Hash, PartialEq)]
+#[derive(Debug, Clone)]
 pub struct Element {
 	pub atomic_number: i32,
 	pub symbol: String,
 	pub name: String,
+	pub atomic_weight: Option<AtomicWeight>,
+	/// On the Pauling scale
+	pub electronegativity: Option<f64>,
+	/// From 1 to 18; None for the lanthanides and actinides
+	pub group: Option<u32>,
+	pub period: Option<u32>,
+	pub block: Option<Block>,
+	pub oxidation_states: Vec<i32>,
+	/// In noble gas notation, e.g. `[Ne] 3s2 3p4`
+	pub electron_configuration: Option<String>,
+	/// Only for isotopes, e.g. 13 for carbon-13. An isotope's atomic weight is its exact isotopic
+	/// mass. Isotopes without their own symbols (unlike D) have symbols like `¹³C`.
+	pub mass_number: Option<u32>,
+	/// Only for isotopes; the fraction of the element's atoms in nature that are this isotope
+	pub abundance: Option<f64>,
+}
+
+/// Elements are identified by their number, symbol, name, and (for isotopes) mass number; the rest
+/// of their data doesn't participate in comparisons.
+impl PartialEq for Element {
+	fn eq(&self, other: &Self) -> bool {
+		self.atomic_number == other.atomic_number
+			&& self.symbol == other.symbol
+			&& self.name == other.name
+			&& self.mass_number == other.mass_number
+	}
+}
+impl Eq for Element {}
+impl Hash for Element {
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		self.atomic_number.hash(state);
+		self.symbol.hash(state);
+		self.name.hash(state);
+		self.mass_number.hash(state);
+	}
+}
+
+/// The electron, treated as a pseudo-element with atomic number 0 so that it can appear in
+/// formulas and equations (e.g. half-reactions). It is not part of any PeriodicTable.
+pub static ELECTRON: LazyLock<Element> = LazyLock::new(|| Element {
+	atomic_weight: Some(AtomicWeight {
+		value: RealNumber::new(5.48579909065e-4, 12),
+		uncertainty: Some(1.6e-14),
+		is_mass_number: false,
+	}),
+	..Element::new(0, "e", "electron")
+});
+
+impl Element {
+	/// Creates an element with no data besides its identity
+	pub fn new(atomic_number: i32, symbol: &str, name: &str) -> Self {
+		Self {
+			atomic_number,
+			symbol: symbol.to_string(),
+			name: name.to_string(),
+			atomic_weight: None,
+			electronegativity: None,
+			group: None,
+			period: None,
+			block: None,
+			oxidation_states: Vec::new(),
+			electron_configuration: None,
+			mass_number: None,
+			abundance: None,
+		}
+	}
+
+	pub fn is_electron(&self) -> bool {
+		self.atomic_number == 0
+	}
+
+	pub fn is_isotope(&self) -> bool {
+		self.mass_number.is_some()
+	}
+
+	/// Whether the element is an isotope with a symbol of its own, e.g. D rather than `²H`
+	fn has_own_symbol(&self) -> bool {
+		self.mass_number
+			.is_some_and(|mass_number| !self.symbol.starts_with(&to_superscript(mass_number)))
+	}
+
+	/// Takes the identity and all known properties of the other element
+	fn update_from(&mut self, other: Element) {
+		self.symbol = other.symbol;
+		self.name = other.name;
+		self.atomic_weight = other.atomic_weight.or(self.atomic_weight.take());
+		self.electronegativity = other.electronegativity.or(self.electronegativity);
+		self.group = other.group.or(self.group);
+		self.period = other.period.or(self.period);
+		self.block = other.block.or(self.block);
+		if !other.oxidation_states.is_empty() {
+			self.oxidation_states = other.oxidation_states;
+		}
+		self.electron_configuration = other
+			.electron_configuration
+			.or(self.electron_configuration.take());
+	}
+}
+
+/// A standard atomic weight in daltons, e.g. `4.002602(2)` for helium. Elements without stable
+/// isotopes instead have the mass number of their longest-lived isotope, written in brackets,
+/// e.g. `[98]` for technetium.
+#[derive(Debug, PartialEq, Clone)]
+pub struct AtomicWeight {
+	/// The significant figures are those of the value as written
+	pub value: RealNumber,
+	/// The standard uncertainty, e.g. 0.000002 for `4.002602(2)`, if it was given
+	pub uncertainty: Option<f64>,
+	pub is_mass_number: bool,
+}
+
+impl FromStr for AtomicWeight {
+	type Err = ParseRealNumberError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		if let Some(mass_number) = s.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+			return Ok(Self {
+				value: RealNumber::exact(mass_number.parse::<RealNumber>()?.value()),
+				uncertainty: None,
+				is_mass_number: true,
+			});
+		}
+
+		// the uncertainty is given in units of the last digit, e.g. `12.0107(8)` is ±0.0008
+		let (value, uncertainty) = match s.split_once('(') {
+			None => (s, None),
+			Some((value, uncertainty)) => {
+				let uncertainty: u32 = uncertainty
+					.strip_suffix(')')
+					.and_then(|digits| digits.parse().ok())
+					.ok_or_else(|| ParseRealNumberError::new(s))?;
+				let decimals = value.split_once('.').map_or(0, |(_, frac)| frac.len());
+				(
+					value,
+					Some(f64::from(uncertainty) / 10f64.powi(decimals as i32)),
+				)
+			},
+		};
+		Ok(Self {
+			value: value.parse()?,
+			uncertainty,
+			is_mass_number: false,
+		})
+	}
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Block {
+	S,
+	P,
+	D,
+	F,
+}
+
+impl FromStr for Block {
+	type Err = ();
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"s" => Ok(Block::S),
+			"p" => Ok(Block::P),
+			"d" => Ok(Block::D),
+			"f" => Ok(Block::F),
+			_ => Err(()),
+		}
+	}
 }
 
 impl fmt::Display for Element {
@@ -106,3 +895,303 @@ impl fmt::Display for Element {
 		)
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parses_extended_lines() {
+		let p_table = PeriodicTable::from(
+			"\
+			# comments and blank lines are ignored
+
+			2 He helium 4.002602(2) - 18 1 s - 1s2
+			6 C carbon 12.011 2.55 14 2 p -4,+4 [He] 2s2 2p2
+			43 Tc technetium [98] 1.9 7 5 d +4,+7"
+				.to_string(),
+		);
+
+		let helium = p_table.get_element("He").unwrap();
+		let weight = helium.atomic_weight.as_ref().unwrap();
+		assert_eq!(weight.value.to_string(), "4.002602");
+		assert!((weight.uncertainty.unwrap() - 0.000002).abs() < 1e-12);
+		assert_eq!(helium.electronegativity, None);
+		assert!(helium.oxidation_states.is_empty());
+		assert_eq!(helium.electron_configuration.as_deref(), Some("1s2"));
+
+		let carbon = p_table.get_element("C").unwrap();
+		assert_eq!(
+			carbon.atomic_weight.as_ref().unwrap().value.sig_figs(),
+			Some(5)
+		);
+		assert_eq!(carbon.atomic_weight.as_ref().unwrap().uncertainty, None);
+		assert_eq!(carbon.electronegativity, Some(2.55));
+		assert_eq!(
+			(carbon.group, carbon.period, carbon.block),
+			(Some(14), Some(2), Some(Block::P))
+		);
+		assert_eq!(carbon.oxidation_states, vec![-4, 4]);
+		assert_eq!(
+			carbon.electron_configuration.as_deref(),
+			Some("[He] 2s2 2p2")
+		);
+
+		let technetium = p_table.get_element("Tc").unwrap();
+		let weight = technetium.atomic_weight.as_ref().unwrap();
+		assert!(weight.is_mass_number);
+		assert_eq!(weight.value.value(), 98.0);
+		assert_eq!(technetium.electron_configuration, None);
+	}
+
+	#[test]
+	fn parses_three_column_lines() {
+		let p_table = PeriodicTable::from("1 H hydrogen\n8 O oxygen 15.999".to_string());
+		let hydrogen = p_table.get_element("H").unwrap();
+		assert_eq!(hydrogen, &Element::new(1, "H", "hydrogen"));
+		assert_eq!(hydrogen.atomic_weight, None);
+		assert_eq!(hydrogen.group, None);
+
+		let oxygen = p_table.get_element("O").unwrap();
+		assert_eq!(
+			oxygen.atomic_weight.as_ref().unwrap().value.to_string(),
+			"15.999"
+		);
+		assert_eq!(oxygen.electronegativity, None);
+	}
+
+	#[test]
+	fn reports_malformed_tables() {
+		let error = |string: &str| string.parse::<PeriodicTable>().unwrap_err();
+
+		assert_eq!(
+			error("1 H hydrogen\n2 He"),
+			LoadTableError {
+				line: 2,
+				column: 5,
+				kind: LoadTableErrorKind::MissingField {
+					expected: "an element name"
+				},
+			}
+		);
+		assert_eq!(
+			error("# header\n  x H hydrogen"),
+			LoadTableError {
+				line: 2,
+				column: 3,
+				kind: LoadTableErrorKind::MalformedField {
+					expected: "a positive integer atomic number",
+					found: "x".to_string(),
+				},
+			}
+		);
+		let weight_error = error("6 C carbon 12.0.1");
+		assert_eq!((weight_error.line, weight_error.column), (1, 12));
+		assert_eq!(
+			weight_error.to_string(),
+			"Line 1, column 12: expected an atomic weight such as 12.011, 4.002602(2), or [98], \
+			 found '12.0.1'"
+		);
+		assert_eq!(error("6 C carbon - - 14 2 q").column, 21);
+		assert_eq!(error("6 C carbon - - - - - +4,x").column, 22);
+
+		let duplicate_symbol = error("1 H hydrogen\n\n  2 H helium");
+		assert_eq!(
+			duplicate_symbol.kind,
+			LoadTableErrorKind::DuplicateSymbol {
+				symbol: "H".to_string(),
+				first_line: 1
+			}
+		);
+		assert_eq!((duplicate_symbol.line, duplicate_symbol.column), (3, 5));
+		let duplicate_number = error("1 H hydrogen\n 1 D deuterium");
+		assert_eq!(
+			duplicate_number.kind,
+			LoadTableErrorKind::DuplicateAtomicNumber {
+				atomic_number: 1,
+				first_line: 1
+			}
+		);
+		assert_eq!((duplicate_number.line, duplicate_number.column), (2, 2));
+		assert!(PeriodicTable::try_from("1 H hydrogen\n2 He helium").is_ok());
+	}
+
+	#[test]
+	fn loads_standard_table() {
+		let p_table = PeriodicTable::standard();
+		assert_eq!(p_table.into_iter().count(), 118);
+		for (i, element) in p_table.into_iter().enumerate() {
+			assert_eq!(element.atomic_number, i as i32 + 1);
+			assert!(element.atomic_weight.is_some(), "{}", element);
+			assert!(element.period.is_some(), "{}", element);
+		}
+
+		let oxygen = p_table.get_element("O").unwrap();
+		assert_eq!(oxygen.name, "oxygen");
+		assert_eq!(
+			oxygen.atomic_weight.as_ref().unwrap().value.to_string(),
+			"15.999"
+		);
+		let iron = p_table.get_element("Fe").unwrap();
+		assert_eq!(iron.electron_configuration.as_deref(), Some("[Ar] 3d6 4s2"));
+		assert_eq!(iron.oxidation_states, vec![2, 3]);
+		assert!(
+			p_table
+				.get_element("Og")
+				.unwrap()
+				.atomic_weight
+				.as_ref()
+				.unwrap()
+				.is_mass_number
+		);
+	}
+
+	#[test]
+	fn overlays_tables() {
+		let mut p_table = PeriodicTable::standard();
+		p_table
+			.overlay(
+				"\
+			1 H protium 1.00783
+			119 Uue ununennium"
+					.parse()
+					.unwrap(),
+			)
+			.unwrap();
+		assert_eq!(p_table.into_iter().count(), 119);
+
+		let hydrogen = p_table.get_element("H").unwrap();
+		assert_eq!(hydrogen.name, "protium");
+		assert_eq!(
+			hydrogen.atomic_weight.as_ref().unwrap().value.to_string(),
+			"1.00783"
+		);
+		// the properties that the overlay leaves unknown are kept
+		assert_eq!(hydrogen.electronegativity, Some(2.20));
+		assert_eq!(p_table.get_element("Uue").unwrap().atomic_number, 119);
+	}
+
+	#[test]
+	fn looks_up_elements() {
+		let mut p_table = PeriodicTable::standard();
+		assert_eq!(p_table.get_element("Fe").unwrap().name, "iron");
+		assert_eq!(p_table.get_element("fe"), None);
+		assert_eq!(p_table.get_by_number(26).unwrap().symbol, "Fe");
+		assert_eq!(p_table.get_by_number(0), None);
+		assert_eq!(p_table.get_by_number(119), None);
+		assert_eq!(p_table.get_by_name("Iron").unwrap().atomic_number, 26);
+		assert_eq!(p_table.get_by_name("IRON").unwrap().atomic_number, 26);
+
+		p_table.overlay("26 Fx ferrum".parse().unwrap()).unwrap();
+		assert_eq!(p_table.get_element("Fe"), None);
+		assert_eq!(p_table.get_by_name("iron"), None);
+		assert_eq!(p_table.get_by_number(26).unwrap().name, "ferrum");
+		assert_eq!(p_table.get_by_name("Ferrum").unwrap().symbol, "Fx");
+	}
+
+	#[test]
+	fn rejects_conflicting_elements() {
+		let mut p_table = PeriodicTable::standard();
+		assert_eq!(
+			p_table.overlay("1 H protium\n119 He ununennium".parse().unwrap()),
+			Err(ElementConflictError::Symbol {
+				symbol: "He".to_string(),
+				atomic_number: 2,
+			})
+		);
+		// a failed overlay leaves the table unchanged
+		assert_eq!(p_table.get_element("H").unwrap().name, "hydrogen");
+		assert_eq!(p_table.get_by_number(119), None);
+		assert_eq!(p_table.get_element("He").unwrap().atomic_number, 2);
+
+		assert_eq!(
+			p_table.overlay("3 Li Helium".parse().unwrap()),
+			Err(ElementConflictError::Name {
+				name: "Helium".to_string(),
+				atomic_number: 2,
+			})
+		);
+		assert_eq!(
+			p_table.add_element(Element::new(2, "Hx", "heliox")),
+			Err(ElementConflictError::AtomicNumber(2))
+		);
+		assert_eq!(
+			p_table.add_isotope(Element::new(2, "Hx", "heliox")),
+			Err(ElementConflictError::NotAnIsotope {
+				symbol: "Hx".to_string(),
+			})
+		);
+		assert_eq!(p_table.get_element("Hx"), None);
+		// an element may keep its own symbol and name
+		p_table
+			.overlay("2 He helium 4.0026".parse().unwrap())
+			.unwrap();
+
+		let error = PeriodicTable::load("1 H hydrogen\n2 He HYDROGEN").unwrap_err();
+		assert_eq!(
+			error.kind,
+			LoadTableErrorKind::DuplicateName {
+				name: "HYDROGEN".to_string(),
+				first_line: 1,
+			}
+		);
+		assert_eq!((error.line, error.column), (2, 6));
+	}
+
+	#[test]
+	fn loads_isotopes() {
+		let p_table = PeriodicTable::standard();
+		let carbon_13 = p_table.get_isotope(6, 13).unwrap();
+		assert_eq!(
+			(carbon_13.symbol.as_str(), carbon_13.name.as_str()),
+			("¹³C", "carbon-13")
+		);
+		assert_eq!(carbon_13.mass_number, Some(13));
+		assert_eq!(
+			carbon_13.atomic_weight.as_ref().unwrap().value.to_string(),
+			"13.00335483507"
+		);
+		assert_ne!(carbon_13, p_table.get_element("C").unwrap());
+		let total_abundance: f64 = p_table
+			.isotopes_of(6)
+			.filter_map(|isotope| isotope.abundance)
+			.sum();
+		assert!((total_abundance - 1.0).abs() < 1e-9);
+
+		let deuterium = p_table.get_element("D").unwrap();
+		assert_eq!(deuterium, p_table.get_isotope(1, 2).unwrap());
+		assert_eq!(deuterium.group, Some(1));
+		assert_eq!(p_table.get_element("¹³C"), None);
+		assert_eq!(p_table.into_iter().count(), 118);
+
+		assert_eq!(
+			"6-13 13.003 0.0107"
+				.parse::<PeriodicTable>()
+				.unwrap_err()
+				.kind,
+			LoadTableErrorKind::UnknownElement { atomic_number: 6 }
+		);
+		assert_eq!(
+			"6 C carbon\n6-13 13.003 -\n6-13 13.003 -"
+				.parse::<PeriodicTable>()
+				.unwrap_err()
+				.kind,
+			LoadTableErrorKind::DuplicateIsotope {
+				atomic_number: 6,
+				mass_number: 13,
+				first_line: 2,
+			}
+		);
+		let duplicate_symbol = "1 H hydrogen\n2 He helium\n1-2 2.014 - He"
+			.parse::<PeriodicTable>()
+			.unwrap_err();
+		assert_eq!(
+			duplicate_symbol.kind,
+			LoadTableErrorKind::DuplicateSymbol {
+				symbol: "He".to_string(),
+				first_line: 2,
+			}
+		);
+		assert_eq!((duplicate_symbol.line, duplicate_symbol.column), (3, 13));
+	}
+}
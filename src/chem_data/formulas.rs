@@ -1,35 +1,432 @@
-use crate::chem_data::elements::Element;
-use crate::helper::coeff_vec::CoeffVec;
+use crate::chem_data::{elements::Element, real_number::RealNumber};
+use crate::helper::{coeff_vec::CoeffVec, superscript::to_superscript};
 use std::{
+	cmp::Ordering,
+	error::Error,
 	fmt,
-	ops::{Add, AddAssign, Mul, MulAssign},
+	hash::{Hash, Hasher},
+	ops::{Add, AddAssign, Mul, MulAssign, Sub},
+	str::FromStr,
 };
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, Clone)]
 pub struct MolecularFormula<'a> {
 	element_count: CoeffVec<&'a Element>,
+	// the net charge in elementary charges, e.g. -2 for SO4^2-
+	charge: i32,
+	// how the formula was written, if it was written in condensed form; this only affects how the
+	// formula is displayed
+	structure: Option<Vec<FormulaPart<'a>>>,
+}
+
+/// A piece of a condensed formula as it was written, e.g. `Ca(OH)2` is an element followed by a
+/// group, and `CuSO4.5H2O` is three elements followed by an adduct
+#[derive(Debug, PartialEq, Clone)]
+pub enum FormulaPart<'a> {
+	/// An element with its subscript, e.g. `O4`
+	Element(&'a Element, u32),
+	/// A parenthesized group with its subscript, e.g. `(OH)2`
+	Group(Vec<FormulaPart<'a>>, u32),
+	/// A dotted addition (such as water of hydration) with its coefficient, e.g. `.5H2O`
+	Adduct(u32, Vec<FormulaPart<'a>>),
+}
+
+/// An order in which to list the elements of a formula
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormulaOrder {
+	/// Carbon first, then hydrogen, then the rest alphabetically; if there is no carbon,
+	/// everything alphabetically, e.g. `CH3Cl` and `ClH`
+	Hill,
+	/// Alphabetically by symbol, e.g. `CH3Cl` becomes `CClH3`
+	Alphabetical,
+	/// By atomic number, then by mass number for isotopes, e.g. `NaCl` stays `NaCl`
+	AtomicNumber,
+}
+
+impl FormulaOrder {
+	fn compare(self, a: &Element, b: &Element) -> Ordering {
+		// isotopes like ¹³C sort as their element, after the element itself
+		let alphabetical = || {
+			sort_symbol(a)
+				.cmp(sort_symbol(b))
+				.then(a.mass_number.cmp(&b.mass_number))
+		};
+		match self {
+			FormulaOrder::Hill => hill_rank(a).cmp(&hill_rank(b)).then_with(alphabetical),
+			FormulaOrder::Alphabetical => alphabetical(),
+			FormulaOrder::AtomicNumber => a
+				.atomic_number
+				.cmp(&b.atomic_number)
+				.then(a.mass_number.cmp(&b.mass_number)),
+		}
+	}
+}
+
+impl FromStr for FormulaOrder {
+	type Err = ();
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"hill" => Ok(FormulaOrder::Hill),
+			"alphabetical" => Ok(FormulaOrder::Alphabetical),
+			"atomic_number" => Ok(FormulaOrder::AtomicNumber),
+			_ => Err(()),
+		}
+	}
+}
+
+/// In Hill order for a formula containing carbon, carbon comes first and hydrogen second,
+/// including their isotopes
+fn hill_rank(element: &Element) -> u8 {
+	match element.atomic_number {
+		6 => 0,
+		1 => 1,
+		_ => 2,
+	}
+}
+
+fn is_carbon(element: &Element) -> bool {
+	element.atomic_number == 6
+}
+
+/// The symbol an element sorts by, i.e. without the mass number of an isotope like `¹³C`
+fn sort_symbol(element: &Element) -> &str {
+	element.symbol.trim_start_matches(is_superscript_digit)
+}
+
+fn is_superscript_digit(c: char) -> bool {
+	matches!(c, '⁰' | '¹' | '²' | '³' | '⁴' | '⁵' | '⁶' | '⁷' | '⁸' | '⁹')
 }
 
 impl<'a> MolecularFormula<'a> {
 	pub fn new() -> Self {
 		MolecularFormula {
 			element_count: CoeffVec::new(),
+			charge: 0,
+			structure: None,
 		}
 	}
 
+	/// Creates the formula for a condensed formula, flattening its groups but keeping them for
+	/// display, e.g. `Ca(OH)2` contains CaO2H2 but still displays as `Ca(OH)2`
+	pub fn from_parts(parts: Vec<FormulaPart<'a>>) -> Result<Self, FormulaArithmeticError> {
+		fn add_parts<'a>(
+			formula: &mut MolecularFormula<'a>,
+			parts: &[FormulaPart<'a>],
+			multiplier: u32,
+		) -> Result<(), FormulaArithmeticError> {
+			let overflow = || FormulaArithmeticError::Overflow;
+			for part in parts {
+				match part {
+					FormulaPart::Element(element, subscript) => {
+						let new_subscript = subscript
+							.checked_mul(multiplier)
+							.and_then(|count| formula.get_subscr(element).checked_add(count))
+							.ok_or_else(overflow)?;
+						formula.try_set_subscr(element, new_subscript)?;
+					},
+					FormulaPart::Group(parts, subscript) => {
+						let multiplier = subscript.checked_mul(multiplier).ok_or_else(overflow)?;
+						add_parts(formula, parts, multiplier)?;
+					},
+					FormulaPart::Adduct(coefficient, parts) => {
+						let multiplier =
+							coefficient.checked_mul(multiplier).ok_or_else(overflow)?;
+						add_parts(formula, parts, multiplier)?;
+					},
+				}
+			}
+			Ok(())
+		}
+
+		let mut result = Self::new();
+		add_parts(&mut result, &parts, 1)?;
+		result.structure = Some(parts);
+		Ok(result)
+	}
+
+	/// Returns how the formula was written if it was written in condensed form
+	pub fn structure(&self) -> Option<&[FormulaPart<'a>]> {
+		self.structure.as_deref()
+	}
+
+	/// Sets the subscript of the element. This discards the condensed structure (if any), since it
+	/// would no longer match the formula.
+	///
+	/// Panics if the subscript is too large to store; use `try_set_subscr` to handle that case.
 	pub fn set_subscr(&mut self, element: &'a Element, new_subscript: u32) {
-		let new_subscript = i32::try_from(new_subscript).unwrap();
+		self.try_set_subscr(element, new_subscript)
+			.expect("The subscript should've fit in the formula");
+	}
+
+	/// Like `set_subscr`, but returns an error instead of panicking if the subscript is too large
+	pub fn try_set_subscr(
+		&mut self,
+		element: &'a Element,
+		new_subscript: u32,
+	) -> Result<(), FormulaArithmeticError> {
+		let new_subscript =
+			i32::try_from(new_subscript).map_err(|_| FormulaArithmeticError::Overflow)?;
 		self.element_count.set_coeff(&element, new_subscript);
+		self.structure = None;
+		Ok(())
 	}
 
 	pub fn get_subscr(&self, element: &'a Element) -> u32 {
 		self.element_count.get_coeff(&element).unsigned_abs()
 	}
+
+	pub fn set_charge(&mut self, new_charge: i32) {
+		self.charge = new_charge;
+	}
+
+	pub fn get_charge(&self) -> i32 {
+		self.charge
+	}
+
+	/// Returns the formula with its subscripts divided by their greatest common divisor, e.g. CH2O
+	/// for C6H12O6. The charge is kept as is, since it belongs to the whole ion, e.g. Hg²⁺ for Hg2²⁺.
+	pub fn empirical(&self) -> Self {
+		let divisor = self
+			.iter()
+			.fold(0, |acc, (_, subscript)| gcd(acc, subscript));
+		let mut result = self.clone();
+		result.structure = None;
+		if divisor == 0 {
+			return result;
+		}
+		for (_, subscript) in &mut result.element_count {
+			*subscript /= divisor as i32;
+		}
+		result
+	}
+
+	/// Sums the atomic weights of the atoms in the formula, in g/mol. The result is rounded
+	/// according to the addition rule for significant figures, and carries the propagated
+	/// uncertainty of the atomic weights. The mass of the electrons gained or lost by an ion is
+	/// ignored unless they're written explicitly, e.g. `$e`.
+	pub fn molar_mass(&self) -> Result<RealNumber, MolarMassError> {
+		let mut total = RealNumber::exact(0.0);
+		for (element, subscript) in self.iter() {
+			total = total
+				.checked_add(element_mass(element, subscript)?)
+				.ok_or(MolarMassError::Overflow)?;
+		}
+		Ok(total)
+	}
+
+	/// Returns the percentage of the formula's mass contributed by each element, in the same order
+	/// as `iter`
+	pub fn mass_percents(&self) -> Result<Vec<(&'a Element, RealNumber)>, MolarMassError> {
+		let total = self.molar_mass()?;
+		self.iter()
+			.map(|(element, subscript)| {
+				let percent = element_mass(element, subscript)?
+					.checked_div(total.clone())
+					.and_then(|fraction| fraction.checked_mul(RealNumber::exact(100.0)))
+					.ok_or(MolarMassError::Overflow)?;
+				Ok((element, percent))
+			})
+			.collect()
+	}
+
+	/// Derives an empirical formula from the mass of each element in a sample, given either in
+	/// grams or as percentages of the total mass, e.g. from a combustion analysis. Mole ratios
+	/// close to simple fractions like 1.5 or 1.33 are scaled up to whole numbers; `tolerance` is
+	/// how far each scaled ratio may be from a whole number, e.g. 0.1.
+	pub fn from_masses(
+		masses: &[(&'a Element, RealNumber)],
+		tolerance: f64,
+	) -> Result<Self, EmpiricalFormulaError> {
+		let mut moles: Vec<(&'a Element, f64)> = Vec::new();
+		for (element, mass) in masses {
+			if mass.value() <= 0.0 {
+				return Err(EmpiricalFormulaError::NonPositiveMass {
+					symbol: element.symbol.clone(),
+				});
+			}
+			let amount = mass.value() / atomic_weight(element)?.value();
+			match moles.iter_mut().find(|(other, _)| other == element) {
+				Some((_, total)) => *total += amount,
+				None => moles.push((element, amount)),
+			}
+		}
+		let smallest = moles
+			.iter()
+			.map(|&(_, amount)| amount)
+			.min_by(f64::total_cmp)
+			.ok_or(EmpiricalFormulaError::NoMasses)?;
+
+		for multiplier in 1..=MAX_EMPIRICAL_MULTIPLIER {
+			let ratios: Vec<f64> = moles
+				.iter()
+				.map(|&(_, amount)| amount / smallest * f64::from(multiplier))
+				.collect();
+			if ratios
+				.iter()
+				.all(|ratio| (ratio - ratio.round()).abs() <= tolerance)
+			{
+				let mut result = Self::new();
+				for (&(element, _), ratio) in moles.iter().zip(ratios) {
+					result.set_subscr(element, ratio.round() as u32);
+				}
+				return Ok(result);
+			}
+		}
+		Err(EmpiricalFormulaError::NoWholeNumberRatio)
+	}
+
+	/// Returns the same formula with its elements listed in the order, e.g. `$HCH3` in Hill order
+	/// is `CH4`. This discards the condensed structure (if any).
+	pub fn sorted(&self, order: FormulaOrder) -> Self {
+		let mut result = self.clone();
+		result.structure = None;
+		if order == FormulaOrder::Hill && !self.iter().any(|(element, _)| is_carbon(element)) {
+			result
+				.element_count
+				.sort_by(|a, b| FormulaOrder::Alphabetical.compare(a, b));
+		} else {
+			result.element_count.sort_by(|a, b| order.compare(a, b));
+		}
+		result
+	}
+
+	/// The canonical form of the formula, for comparing formulas as text, e.g. in a database
+	pub fn hill(&self) -> Self {
+		self.sorted(FormulaOrder::Hill)
+	}
+
+	/// Compares formulas the way a chemical index orders them: symbol by symbol through their Hill
+	/// formulas, with fewer atoms of an element first, e.g. `CH4`, `C2H6`, `CO2`, `H2O`. Isotopes
+	/// sort with their element, and formulas with the same atoms by their charge.
+	pub fn cmp_hill(&self, other: &Self) -> Ordering {
+		let index_key = |formula: &Self| -> Vec<(String, Option<u32>, u32)> {
+			formula
+				.hill()
+				.iter()
+				.map(|(element, subscript)| {
+					let symbol = sort_symbol(element).to_string();
+					(symbol, element.mass_number, subscript)
+				})
+				.collect()
+		};
+		index_key(self)
+			.cmp(&index_key(other))
+			.then(self.charge.cmp(&other.charge))
+	}
+
+	/// Adds the atoms and charge of another formula, returning an error instead of panicking if a
+	/// subscript or the charge overflows
+	pub fn checked_add(mut self, rhs: Self) -> Result<Self, FormulaArithmeticError> {
+		for (element, subscript) in rhs.iter() {
+			let new_subscript = self
+				.get_subscr(element)
+				.checked_add(subscript)
+				.ok_or(FormulaArithmeticError::Overflow)?;
+			self.try_set_subscr(element, new_subscript)?;
+		}
+		self.charge = self
+			.charge
+			.checked_add(rhs.charge)
+			.ok_or(FormulaArithmeticError::Overflow)?;
+		self.structure = None;
+		Ok(self)
+	}
+
+	/// Removes the atoms and charge of a fragment, e.g. the water of hydration from `CuSO4·5H2O`.
+	/// Returns an error if the formula doesn't contain every atom of the fragment.
+	pub fn checked_sub(mut self, rhs: Self) -> Result<Self, FormulaArithmeticError> {
+		for (element, subscript) in rhs.iter() {
+			let available = self.get_subscr(element);
+			let new_subscript = available.checked_sub(subscript).ok_or_else(|| {
+				FormulaArithmeticError::NegativeSubscript {
+					symbol: element.symbol.clone(),
+					available,
+					removed: subscript,
+				}
+			})?;
+			self.try_set_subscr(element, new_subscript)?;
+		}
+		self.charge = self
+			.charge
+			.checked_sub(rhs.charge)
+			.ok_or(FormulaArithmeticError::Overflow)?;
+		self.structure = None;
+		Ok(self)
+	}
+
+	/// Multiplies every subscript and the charge, returning an error instead of panicking if any of
+	/// them overflows
+	pub fn checked_mul(mut self, rhs: u32) -> Result<Self, FormulaArithmeticError> {
+		let product = |value: i32| {
+			i32::try_from(rhs)
+				.ok()
+				.and_then(|rhs| value.checked_mul(rhs))
+				.ok_or(FormulaArithmeticError::Overflow)
+		};
+		for (element, subscript) in self.element_count.clone() {
+			self.element_count.set_coeff(&element, product(subscript)?);
+		}
+		self.charge = product(self.charge)?;
+		self.structure = None;
+		Ok(self)
+	}
+
+	/// Iterates over each element in the formula along with its subscript
+	pub fn iter(&self) -> impl Iterator<Item = (&'a Element, u32)> + '_ {
+		self.element_count
+			.iter()
+			.map(|&(element, subscript)| (element, subscript.unsigned_abs()))
+	}
 }
 
+/// The largest factor by which `from_masses` scales mole ratios looking for whole numbers
+const MAX_EMPIRICAL_MULTIPLIER: u32 = 10;
+
+/// The mass of `count` atoms of the element in g/mol, with the uncertainty of its atomic weight
+fn element_mass(element: &Element, count: u32) -> Result<RealNumber, MolarMassError> {
+	// atoms of the same element share the error in its atomic weight, so their uncertainties add
+	// linearly, which multiplying by the exact count does
+	atomic_weight(element)?
+		.checked_mul(RealNumber::exact(f64::from(count)))
+		.ok_or(MolarMassError::Overflow)
+}
+
+/// The atomic weight of the element in g/mol, along with its uncertainty
+fn atomic_weight(element: &Element) -> Result<RealNumber, UnknownAtomicWeightError> {
+	let Some(weight) = &element.atomic_weight else {
+		return Err(UnknownAtomicWeightError {
+			symbol: element.symbol.clone(),
+		});
+	};
+	// a weight given without an uncertainty is taken to be uncertain by one in its last digit, as
+	// in abridged tables
+	let uncertainty = weight.uncertainty.or_else(|| {
+		weight
+			.value
+			.last_digit_place()
+			.map(|place| 10f64.powi(place))
+	});
+	let mut weight_value = weight.value.clone();
+	if let Some(uncertainty) = uncertainty {
+		weight_value = weight_value.with_uncertainty(uncertainty);
+	}
+	Ok(weight_value)
+}
+
+fn gcd(mut a: u32, mut b: u32) -> u32 {
+	while b != 0 {
+		(a, b) = (b, a % b);
+	}
+	a
+}
+
+/// Panics on overflow; use `checked_add` to handle it
 impl<'a> AddAssign for MolecularFormula<'a> {
 	fn add_assign(&mut self, rhs: Self) {
-		self.element_count += rhs.element_count;
+		*self = std::mem::take(self)
+			.checked_add(rhs)
+			.expect("Adding formulas should not have overflowed");
 	}
 }
 
@@ -42,9 +439,22 @@ impl<'a> Add for MolecularFormula<'a> {
 	}
 }
 
+/// Subtraction fails if the formula doesn't contain the whole fragment being removed, so it
+/// returns a Result rather than a formula
+impl<'a> Sub for MolecularFormula<'a> {
+	type Output = Result<Self, FormulaArithmeticError>;
+
+	fn sub(self, rhs: Self) -> Self::Output {
+		self.checked_sub(rhs)
+	}
+}
+
+/// Panics on overflow; use `checked_mul` to handle it
 impl<'a> MulAssign<u32> for MolecularFormula<'a> {
 	fn mul_assign(&mut self, rhs: u32) {
-		self.element_count *= i32::try_from(rhs).unwrap();
+		*self = std::mem::take(self)
+			.checked_mul(rhs)
+			.expect("Multiplying a formula should not have overflowed");
 	}
 }
 
@@ -57,30 +467,317 @@ impl<'a> Mul<u32> for MolecularFormula<'a> {
 	}
 }
 
+/// Formulas are equal if they have the same elements and charge, regardless of how they were
+/// written, e.g. `Ca(OH)2` is equal to `CaO2H2` and `HCH3` is equal to `CH4`
+impl PartialEq for MolecularFormula<'_> {
+	fn eq(&self, other: &Self) -> bool {
+		self.element_count == other.element_count && self.charge == other.charge
+	}
+}
+
+impl Eq for MolecularFormula<'_> {}
+
+/// Consistent with equality, so the order of the elements doesn't affect the hash
+impl Hash for MolecularFormula<'_> {
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		let mut pairs: Vec<(&Element, u32)> = self.iter().collect();
+		pairs.sort_by(|(a, _), (b, _)| {
+			FormulaOrder::AtomicNumber
+				.compare(a, b)
+				.then_with(|| a.symbol.cmp(&b.symbol))
+				.then_with(|| a.name.cmp(&b.name))
+		});
+		pairs.hash(state);
+		self.charge.hash(state);
+	}
+}
+
+/// Displays the formula with its charge as a superscript suffix, e.g. `SO4²⁻` or `NH4⁺`.
+/// Condensed formulas are displayed as written, except with a `·` before each adduct, e.g.
+/// `CuSO4·5H2O`
 impl fmt::Display for MolecularFormula<'_> {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-		for &(element, subscript) in &self.element_count {
-			write!(
-				f,
-				"{}{}",
-				element.symbol,
-				if subscript == 1 {
-					"".to_string()
-				} else {
-					subscript.to_string()
+		match &self.structure {
+			Some(parts) => fmt_parts(parts, f)?,
+			None => {
+				for &(element, subscript) in &self.element_count {
+					write!(f, "{}", element.symbol)?;
+					fmt_subscript(subscript.unsigned_abs(), f)?;
 				}
-			)?;
+			},
+		}
+		if self.charge != 0 {
+			if self.charge.unsigned_abs() != 1 {
+				write!(f, "{}", to_superscript(self.charge.unsigned_abs()))?;
+			}
+			write!(f, "{}", if self.charge > 0 { '⁺' } else { '⁻' })?;
 		}
 		Ok(())
 	}
 }
 
+fn fmt_parts(parts: &[FormulaPart], f: &mut fmt::Formatter<'_>) -> fmt::Result {
+	for part in parts {
+		match part {
+			FormulaPart::Element(element, subscript) => {
+				write!(f, "{}", element.symbol)?;
+				fmt_subscript(*subscript, f)?;
+			},
+			FormulaPart::Group(parts, subscript) => {
+				write!(f, "(")?;
+				fmt_parts(parts, f)?;
+				write!(f, ")")?;
+				fmt_subscript(*subscript, f)?;
+			},
+			FormulaPart::Adduct(coefficient, parts) => {
+				write!(f, "·")?;
+				fmt_subscript(*coefficient, f)?;
+				fmt_parts(parts, f)?;
+			},
+		}
+	}
+	Ok(())
+}
+
+/// Writes the subscript (or coefficient), which is omitted if it is one
+fn fmt_subscript(subscript: u32, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+	if subscript != 1 {
+		write!(f, "{}", subscript)?;
+	}
+	Ok(())
+}
+
+#[derive(Debug, PartialEq)]
+pub struct UnknownAtomicWeightError {
+	pub symbol: String,
+}
+impl fmt::Display for UnknownAtomicWeightError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "The atomic weight of {} is unknown", self.symbol)
+	}
+}
+impl Error for UnknownAtomicWeightError {}
+
+#[derive(Debug, PartialEq)]
+pub enum MolarMassError {
+	UnknownAtomicWeight(UnknownAtomicWeightError),
+	/// The mass, or a percentage of it, is too large to store
+	Overflow,
+}
+impl fmt::Display for MolarMassError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			MolarMassError::UnknownAtomicWeight(err) => fmt::Display::fmt(err, f),
+			MolarMassError::Overflow => write!(f, "The molar mass is too large"),
+		}
+	}
+}
+impl Error for MolarMassError {}
+impl From<UnknownAtomicWeightError> for MolarMassError {
+	fn from(value: UnknownAtomicWeightError) -> Self {
+		MolarMassError::UnknownAtomicWeight(value)
+	}
+}
+
+#[derive(Debug, PartialEq)]
+pub enum FormulaArithmeticError {
+	/// A fragment can't be removed from a formula that has fewer atoms of one of its elements
+	NegativeSubscript {
+		symbol: String,
+		available: u32,
+		removed: u32,
+	},
+	/// A subscript or the charge is too large to store
+	Overflow,
+}
+impl fmt::Display for FormulaArithmeticError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			FormulaArithmeticError::NegativeSubscript {
+				symbol,
+				available,
+				removed,
+			} => write!(
+				f,
+				"Cannot remove {} {} atom(s) from a formula with only {}",
+				removed, symbol, available
+			),
+			FormulaArithmeticError::Overflow => {
+				write!(f, "A subscript or charge is too large")
+			},
+		}
+	}
+}
+impl Error for FormulaArithmeticError {}
+
+#[derive(Debug, PartialEq)]
+pub enum EmpiricalFormulaError {
+	UnknownAtomicWeight(UnknownAtomicWeightError),
+	/// No elements were given
+	NoMasses,
+	NonPositiveMass {
+		symbol: String,
+	},
+	/// The mole ratios aren't close enough to any simple whole-number ratio
+	NoWholeNumberRatio,
+}
+impl fmt::Display for EmpiricalFormulaError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			EmpiricalFormulaError::UnknownAtomicWeight(err) => fmt::Display::fmt(err, f),
+			EmpiricalFormulaError::NoMasses => {
+				write!(f, "At least one element is needed for an empirical formula")
+			},
+			EmpiricalFormulaError::NonPositiveMass { symbol } => {
+				write!(f, "The mass of {} must be positive", symbol)
+			},
+			EmpiricalFormulaError::NoWholeNumberRatio => write!(
+				f,
+				"The mole ratios are not close to any whole-number ratio with a multiplier of up \
+				 to {}",
+				MAX_EMPIRICAL_MULTIPLIER
+			),
+		}
+	}
+}
+impl Error for EmpiricalFormulaError {}
+impl From<UnknownAtomicWeightError> for EmpiricalFormulaError {
+	fn from(value: UnknownAtomicWeightError) -> Self {
+		EmpiricalFormulaError::UnknownAtomicWeight(value)
+	}
+}
+
 impl Default for MolecularFormula<'_> {
 	fn default() -> Self {
 		Self::new()
 	}
 }
 
+#[cfg(test)]
+pub(crate) mod tests {
+	use super::*;
+	use crate::chem_data::elements::PeriodicTable;
+	use std::collections::HashSet;
+
+	/// Builds a formula from element symbols and their subscripts, e.g. `&[("H", 2), ("O", 1)]`
+	pub(crate) fn formula<'a>(
+		p_table: &'a PeriodicTable,
+		parts: &[(&str, u32)],
+	) -> MolecularFormula<'a> {
+		let mut result = MolecularFormula::new();
+		for &(symbol, subscript) in parts {
+			result.set_subscr(p_table.get_element(symbol).unwrap(), subscript);
+		}
+		result
+	}
+
+	#[test]
+	fn sorts_in_hill_order() {
+		let p_table = PeriodicTable::standard();
+		let cases: [(&[(&str, u32)], &str); 4] = [
+			(&[("H", 1), ("C", 1), ("H", 3)], "CH3"),
+			(&[("Cl", 1), ("H", 3), ("C", 1)], "CH3Cl"),
+			(&[("O", 1), ("H", 2)], "H2O"),
+			(&[("Na", 1), ("Cl", 1)], "ClNa"),
+		];
+		for (parts, hill) in cases {
+			assert_eq!(formula(&p_table, parts).hill().to_string(), hill);
+		}
+
+		let mut labeled = formula(&p_table, &[("Br", 1), ("H", 6), ("C", 1)]);
+		labeled.set_subscr(p_table.get_isotope(6, 13).unwrap(), 1);
+		assert_eq!(labeled.hill().to_string(), "C¹³CH6Br");
+
+		let chloromethane = formula(&p_table, &[("Cl", 1), ("H", 3), ("C", 1)]);
+		assert_eq!(
+			chloromethane.sorted(FormulaOrder::Alphabetical).to_string(),
+			"CClH3"
+		);
+		assert_eq!(
+			chloromethane.sorted(FormulaOrder::AtomicNumber).to_string(),
+			"H3CCl"
+		);
+	}
+
+	#[test]
+	fn compares_regardless_of_order() {
+		let p_table = PeriodicTable::standard();
+		let methane = formula(&p_table, &[("C", 1), ("H", 4)]);
+		let reordered = formula(&p_table, &[("H", 4), ("C", 1)]);
+		assert_eq!(methane, reordered);
+		assert_ne!(methane, formula(&p_table, &[("C", 1), ("H", 3)]));
+
+		let set: HashSet<_> = [methane.clone(), reordered, methane.hill()]
+			.into_iter()
+			.collect();
+		assert_eq!(set.len(), 1);
+	}
+
+	#[test]
+	fn finds_empirical_formulas() {
+		let p_table = PeriodicTable::standard();
+		let glucose = formula(&p_table, &[("C", 6), ("H", 12), ("O", 6)]);
+		assert_eq!(glucose.empirical().to_string(), "CH2O");
+
+		let mut mercury_i = formula(&p_table, &[("Hg", 2)]);
+		mercury_i.set_charge(2);
+		let empirical = mercury_i.empirical();
+		assert_eq!(empirical.get_subscr(p_table.get_element("Hg").unwrap()), 1);
+		assert_eq!(empirical.get_charge(), 2);
+		assert_eq!(MolecularFormula::new().empirical(), MolecularFormula::new());
+	}
+
+	#[test]
+	fn subtracts_fragments() {
+		let p_table = PeriodicTable::standard();
+		let hydrate = formula(&p_table, &[("Cu", 1), ("S", 1), ("O", 9), ("H", 10)]);
+		let water = formula(&p_table, &[("H", 2), ("O", 1)]);
+		let anhydrous = formula(&p_table, &[("Cu", 1), ("S", 1), ("O", 4)]);
+		assert_eq!(hydrate - water.clone() * 5, Ok(anhydrous.clone()));
+		assert_eq!(anhydrous.to_string(), "CuSO4");
+		assert_eq!(
+			anhydrous - water.clone(),
+			Err(FormulaArithmeticError::NegativeSubscript {
+				symbol: "H".to_string(),
+				available: 0,
+				removed: 2,
+			})
+		);
+
+		let mut hydroxide = formula(&p_table, &[("O", 1), ("H", 1)]);
+		hydroxide.set_charge(-1);
+		let oxide = (hydroxide.clone() - formula(&p_table, &[("H", 1)])).unwrap();
+		assert_eq!(oxide.get_charge(), -1);
+		let mut proton = formula(&p_table, &[("H", 1)]);
+		proton.set_charge(1);
+		assert_eq!((hydroxide - proton).unwrap().get_charge(), -2);
+	}
+
+	#[test]
+	fn reports_overflow() {
+		let p_table = PeriodicTable::standard();
+		let hydrogen = p_table.get_element("H").unwrap();
+		let mut formula = MolecularFormula::new();
+		assert_eq!(
+			formula.try_set_subscr(hydrogen, u32::MAX),
+			Err(FormulaArithmeticError::Overflow)
+		);
+		formula.set_subscr(hydrogen, 1 << 30);
+		assert_eq!(
+			formula.clone().checked_add(formula.clone()),
+			Err(FormulaArithmeticError::Overflow)
+		);
+		assert_eq!(
+			formula.clone().checked_mul(2),
+			Err(FormulaArithmeticError::Overflow)
+		);
+		assert_eq!(
+			formula.checked_mul(1).map(|f| f.get_subscr(hydrogen)),
+			Ok(1 << 30)
+		);
+	}
+}
+
 // #[cfg(test)]
 // mod tests {
 // 	use super::*;
@@ -0,0 +1,373 @@
+use super::{
+	chem_eqn::BalanceError,
+	chem_eqn::ChemEqn,
+	dictionary::{BadTypeError, DataType, Value},
+	elements::Element,
+	formulas::{EmpiricalFormulaError, FormulaOrder, MolarMassError, MolecularFormula},
+	quantity::Quantity,
+	real_number::RealNumber,
+};
+use std::{
+	collections::{BTreeMap, HashMap},
+	error::Error,
+	fmt::Display,
+};
+
+/// A method callable on a value with the `.` operator, e.g. `$C6H12O6.empirical`. The first
+/// parameter is the receiver (the value before the `.`) and the second is the arguments, of which
+/// there are always as many as the method was registered with.
+pub type Method = for<'a> fn(Value<'a>, Vec<Value<'a>>) -> Result<Value<'a>, MethodError<'a>>;
+
+/// The methods available for each DataType. Library users can register their own methods (or
+/// replace built-in ones) with `register`.
+#[derive(Debug, Clone, Default)]
+pub struct MethodRegistry {
+	methods: HashMap<DataType, BTreeMap<String, (usize, Method)>>,
+}
+
+impl MethodRegistry {
+	/// Creates a registry with no methods at all.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Creates a registry with all of the built-in methods.
+	pub fn with_builtins() -> Self {
+		let mut registry = Self::new();
+		registry.register(DataType::ElementRef, "name", 0, element_name);
+		registry.register(DataType::ElementRef, "symbol", 0, element_symbol);
+		registry.register(DataType::ElementRef, "number", 0, element_number);
+		registry.register(
+			DataType::MolecularFormula,
+			"empirical",
+			0,
+			formula_empirical,
+		);
+		registry.register(DataType::MolecularFormula, "atoms", 0, formula_atoms);
+		registry.register(DataType::MolecularFormula, "count", 1, formula_count);
+		registry.register(DataType::MolecularFormula, "mass", 0, formula_mass);
+		registry.register(DataType::MolecularFormula, "percents", 0, formula_percents);
+		registry.register(DataType::MolecularFormula, "hill", 0, formula_hill);
+		registry.register(DataType::MolecularFormula, "sorted", 1, formula_sorted);
+		registry.register(DataType::ChemEqn, "balance", 0, eqn_balance);
+		registry.register(DataType::Quantity, "value", 0, quantity_value);
+		registry.register(DataType::Quantity, "units", 0, quantity_units);
+		registry.register(DataType::Tuple, "len", 0, tuple_len);
+		registry.register(DataType::Tuple, "get", 1, tuple_get);
+		registry.register(DataType::Tuple, "empirical", 0, tuple_empirical);
+		registry
+	}
+
+	/// Registers a method taking the specified number of arguments, replacing any method of the
+	/// same name for the same type.
+	pub fn register(&mut self, data_type: DataType, name: &str, num_args: usize, method: Method) {
+		self.methods
+			.entry(data_type)
+			.or_default()
+			.insert(name.to_string(), (num_args, method));
+	}
+
+	/// Returns the names of all methods available for the type, in alphabetical order.
+	pub fn available(&self, data_type: DataType) -> Vec<&str> {
+		match self.methods.get(&data_type) {
+			None => Vec::new(),
+			Some(methods) => methods.keys().map(String::as_str).collect(),
+		}
+	}
+
+	pub fn call<'a>(
+		&self,
+		receiver: Value<'a>,
+		name: &str,
+		args: Vec<Value<'a>>,
+	) -> Result<Value<'a>, MethodError<'a>> {
+		let data_type = DataType::from(&receiver);
+		let Some(&(num_args, method)) = self
+			.methods
+			.get(&data_type)
+			.and_then(|methods| methods.get(name))
+		else {
+			return Err(MethodError::UnknownMethod {
+				data_type,
+				name: name.to_string(),
+				available: self
+					.available(data_type)
+					.into_iter()
+					.map(str::to_string)
+					.collect(),
+			});
+		};
+		if args.len() != num_args {
+			return Err(MethodError::ArgumentCount {
+				name: name.to_string(),
+				expected: num_args,
+				found: args.len(),
+			});
+		}
+		method(receiver, args)
+	}
+}
+
+#[derive(Debug)]
+pub enum MethodError<'a> {
+	UnknownMethod {
+		data_type: DataType,
+		name: String,
+		available: Vec<String>,
+	},
+	ArgumentCount {
+		name: String,
+		expected: usize,
+		found: usize,
+	},
+	BadType(BadTypeError<'a>),
+	Balance(BalanceError),
+	MolarMass(MolarMassError),
+	EmpiricalFormula(EmpiricalFormulaError),
+	/// An error from a method registered outside of this crate
+	Other(Box<dyn Error>),
+}
+impl Display for MethodError<'_> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			MethodError::UnknownMethod {
+				data_type,
+				name,
+				available,
+			} => {
+				write!(f, "A {} has no method '{}'", data_type, name)?;
+				if available.is_empty() {
+					write!(f, "; it has no methods at all")
+				} else {
+					write!(f, "; available methods are: {}", available.join(", "))
+				}
+			},
+			MethodError::ArgumentCount {
+				name,
+				expected,
+				found,
+			} => write!(
+				f,
+				"Method '{}' takes {} argument(s) but was given {}",
+				name, expected, found
+			),
+			MethodError::BadType(err) => Display::fmt(err, f),
+			MethodError::Balance(err) => Display::fmt(err, f),
+			MethodError::MolarMass(err) => Display::fmt(err, f),
+			MethodError::EmpiricalFormula(err) => Display::fmt(err, f),
+			MethodError::Other(err) => Display::fmt(err, f),
+		}
+	}
+}
+impl Error for MethodError<'_> {}
+impl<'a> From<BadTypeError<'a>> for MethodError<'a> {
+	fn from(value: BadTypeError<'a>) -> Self {
+		MethodError::BadType(value)
+	}
+}
+impl From<BalanceError> for MethodError<'_> {
+	fn from(value: BalanceError) -> Self {
+		MethodError::Balance(value)
+	}
+}
+impl From<MolarMassError> for MethodError<'_> {
+	fn from(value: MolarMassError) -> Self {
+		MethodError::MolarMass(value)
+	}
+}
+impl From<EmpiricalFormulaError> for MethodError<'_> {
+	fn from(value: EmpiricalFormulaError) -> Self {
+		MethodError::EmpiricalFormula(value)
+	}
+}
+
+fn element_name<'a>(
+	receiver: Value<'a>,
+	_args: Vec<Value<'a>>,
+) -> Result<Value<'a>, MethodError<'a>> {
+	Ok(Value::String(receiver.as_type::<&Element>()?.name.clone()))
+}
+
+fn element_symbol<'a>(
+	receiver: Value<'a>,
+	_args: Vec<Value<'a>>,
+) -> Result<Value<'a>, MethodError<'a>> {
+	Ok(Value::String(
+		receiver.as_type::<&Element>()?.symbol.clone(),
+	))
+}
+
+fn element_number<'a>(
+	receiver: Value<'a>,
+	_args: Vec<Value<'a>>,
+) -> Result<Value<'a>, MethodError<'a>> {
+	let atomic_number = receiver.as_type::<&Element>()?.atomic_number;
+	Ok(Value::Integer(atomic_number.unsigned_abs()))
+}
+
+fn formula_empirical<'a>(
+	receiver: Value<'a>,
+	_args: Vec<Value<'a>>,
+) -> Result<Value<'a>, MethodError<'a>> {
+	Ok(Value::MolecularFormula(
+		receiver.as_type::<MolecularFormula>()?.empirical(),
+	))
+}
+
+fn formula_atoms<'a>(
+	receiver: Value<'a>,
+	_args: Vec<Value<'a>>,
+) -> Result<Value<'a>, MethodError<'a>> {
+	let formula = receiver.as_type::<MolecularFormula>()?;
+	Ok(Value::Integer(
+		formula.iter().map(|(_, subscript)| subscript).sum(),
+	))
+}
+
+fn formula_count<'a>(
+	receiver: Value<'a>,
+	args: Vec<Value<'a>>,
+) -> Result<Value<'a>, MethodError<'a>> {
+	let formula = receiver.as_type::<MolecularFormula>()?;
+	let [element] = expect_args(args);
+	Ok(Value::Integer(formula.get_subscr(element.as_type()?)))
+}
+
+fn formula_mass<'a>(
+	receiver: Value<'a>,
+	_args: Vec<Value<'a>>,
+) -> Result<Value<'a>, MethodError<'a>> {
+	let formula = receiver.as_type::<MolecularFormula>()?;
+	Ok(Value::RealNumber(formula.molar_mass()?))
+}
+
+fn formula_percents<'a>(
+	receiver: Value<'a>,
+	_args: Vec<Value<'a>>,
+) -> Result<Value<'a>, MethodError<'a>> {
+	let formula = receiver.as_type::<MolecularFormula>()?;
+	Ok(Value::Tuple(
+		formula
+			.mass_percents()?
+			.into_iter()
+			.map(|(element, percent)| Value::Tuple(vec![element.into(), percent.into()]))
+			.collect(),
+	))
+}
+
+fn formula_hill<'a>(
+	receiver: Value<'a>,
+	_args: Vec<Value<'a>>,
+) -> Result<Value<'a>, MethodError<'a>> {
+	Ok(Value::MolecularFormula(
+		receiver.as_type::<MolecularFormula>()?.hill(),
+	))
+}
+
+fn formula_sorted<'a>(
+	receiver: Value<'a>,
+	args: Vec<Value<'a>>,
+) -> Result<Value<'a>, MethodError<'a>> {
+	let formula = receiver.as_type::<MolecularFormula>()?;
+	let [order] = expect_args(args);
+	let order = order.as_type::<String>()?;
+	let order = order.parse::<FormulaOrder>().map_err(|()| {
+		MethodError::Other(
+			format!(
+				"Unknown order '{}'; expected \"hill\", \"alphabetical\", or \"atomic_number\"",
+				order
+			)
+			.into(),
+		)
+	})?;
+	Ok(Value::MolecularFormula(formula.sorted(order)))
+}
+
+fn quantity_value<'a>(
+	receiver: Value<'a>,
+	_args: Vec<Value<'a>>,
+) -> Result<Value<'a>, MethodError<'a>> {
+	Ok(Value::RealNumber(
+		receiver.as_type::<Quantity>()?.value().clone(),
+	))
+}
+
+fn quantity_units<'a>(
+	receiver: Value<'a>,
+	_args: Vec<Value<'a>>,
+) -> Result<Value<'a>, MethodError<'a>> {
+	Ok(Value::String(
+		receiver.as_type::<Quantity>()?.units().to_string(),
+	))
+}
+
+fn tuple_len<'a>(receiver: Value<'a>, _args: Vec<Value<'a>>) -> Result<Value<'a>, MethodError<'a>> {
+	let values = receiver.as_type::<Vec<Value>>()?;
+	Ok(Value::Integer(
+		u32::try_from(values.len()).map_err(|err| MethodError::Other(Box::new(err)))?,
+	))
+}
+
+fn tuple_get<'a>(receiver: Value<'a>, args: Vec<Value<'a>>) -> Result<Value<'a>, MethodError<'a>> {
+	let values = receiver.as_type::<Vec<Value>>()?;
+	let [index] = expect_args(args);
+	let index = index.as_type::<u32>()?;
+	let len = values.len();
+	values.into_iter().nth(index as usize).ok_or_else(|| {
+		MethodError::Other(
+			format!(
+				"Index {} is out of bounds for a tuple of length {}",
+				index, len
+			)
+			.into(),
+		)
+	})
+}
+
+/// How far from a whole number each scaled mole ratio may be in `tuple_empirical`
+const EMPIRICAL_TOLERANCE: f64 = 0.1;
+
+/// Derives an empirical formula from a tuple of (element, mass) pairs, e.g.
+/// `((C, 40.0), (H, 6.7), (O, 53.3)).empirical`, where the masses are in grams or percent
+fn tuple_empirical<'a>(
+	receiver: Value<'a>,
+	_args: Vec<Value<'a>>,
+) -> Result<Value<'a>, MethodError<'a>> {
+	let mut masses = Vec::new();
+	for pair in receiver.as_type::<Vec<Value>>()? {
+		let [element, mass]: [Value; 2] =
+			pair.as_type::<Vec<Value>>()?
+				.try_into()
+				.map_err(|pair: Vec<Value>| {
+					MethodError::Other(
+						format!(
+							"Expected an (element, mass) pair, found {} values",
+							pair.len()
+						)
+						.into(),
+					)
+				})?;
+		let mass = match mass {
+			Value::Integer(integer) => RealNumber::exact(f64::from(integer)),
+			mass => mass.as_type()?,
+		};
+		masses.push((element.as_type::<&Element>()?, mass));
+	}
+	Ok(Value::MolecularFormula(MolecularFormula::from_masses(
+		&masses,
+		EMPIRICAL_TOLERANCE,
+	)?))
+}
+
+/// Unpacks the arguments of a method; the registry already checked how many there are.
+fn expect_args<const N: usize>(args: Vec<Value>) -> [Value; N] {
+	args.try_into()
+		.unwrap_or_else(|_| unreachable!("The registry should've checked the number of arguments"))
+}
+
+fn eqn_balance<'a>(
+	receiver: Value<'a>,
+	_args: Vec<Value<'a>>,
+) -> Result<Value<'a>, MethodError<'a>> {
+	Ok(Value::ChemEqn(receiver.as_type::<ChemEqn>()?.balance()?))
+}
@@ -0,0 +1,520 @@
+use std::{error::Error, fmt::Display, str::FromStr};
+
+use super::real_number::{ParseRealNumberError, RealNumber};
+use crate::helper::coeff_vec::{CoeffVec, Num};
+
+/// A product of units raised to integer powers, e.g. `g/mol` or `m/s^2`. Units are only ever
+/// combined, never converted, so `g` and `kg` are unrelated units.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Units {
+	powers: CoeffVec<String>,
+}
+
+impl Units {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// A single unit, e.g. `mol`
+	pub fn of(symbol: &str) -> Self {
+		let mut result = Self::new();
+		result.powers.set_coeff(&symbol.to_string(), 1);
+		result
+	}
+
+	pub fn is_dimensionless(&self) -> bool {
+		self.powers.is_empty()
+	}
+
+	/// Iterates over each unit along with its power, in the order they were written
+	pub fn iter(&self) -> impl Iterator<Item = &(String, Num)> {
+		self.powers.iter()
+	}
+
+	/// Raises each unit to the power, or returns None if a power would overflow
+	pub fn checked_powi(self, power: Num) -> Option<Self> {
+		Some(Self {
+			powers: self.powers.checked_mul(power)?,
+		})
+	}
+
+	/// Multiplies the units by adding their powers, or returns None if a power would overflow
+	pub fn checked_mul(self, rhs: Self) -> Option<Self> {
+		Some(Self {
+			powers: self.powers.checked_add(rhs.powers)?,
+		})
+	}
+
+	/// Divides the units by subtracting their powers, or returns None if a power would overflow
+	pub fn checked_div(self, rhs: Self) -> Option<Self> {
+		self.checked_mul(rhs.checked_powi(-1)?)
+	}
+}
+
+/// Displays the units with positive powers first and then a `/` before each unit with a negative
+/// power, e.g. `kg*m^2/s^2`. Units that are all negative powers are written as such, e.g. `s^-1`.
+impl Display for Units {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let write_unit = |f: &mut std::fmt::Formatter<'_>, symbol: &str, power: Num| {
+			write!(f, "{}", symbol)?;
+			if power != 1 {
+				write!(f, "^{}", power)?;
+			}
+			Ok(())
+		};
+
+		let has_numerator = self.iter().any(|(_, power)| *power > 0);
+		let mut first = true;
+		for (symbol, power) in self.iter() {
+			if has_numerator && *power < 0 {
+				continue;
+			}
+			if !first {
+				write!(f, "*")?;
+			}
+			first = false;
+			write_unit(f, symbol, *power)?;
+		}
+		if has_numerator {
+			for (symbol, power) in self.iter().filter(|(_, power)| *power < 0) {
+				write!(f, "/")?;
+				write_unit(f, symbol, -power)?;
+			}
+		}
+		Ok(())
+	}
+}
+
+/// Parses units such as `g/mol`, `mol L^-1` or `J/(mol*K)`. Units written next to each other or
+/// joined by `*` are multiplied, and a `/` divides by only the unit or parenthesized group right
+/// after it.
+impl FromStr for Units {
+	type Err = ParseQuantityError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let mut reader = UnitsReader {
+			chars: s.chars().collect(),
+			position: 0,
+		};
+		let units = reader.read_product()?;
+		match reader.peek() {
+			None => Ok(units),
+			Some(_) => Err(reader.unexpected()),
+		}
+	}
+}
+
+struct UnitsReader {
+	chars: Vec<char>,
+	position: usize,
+}
+
+impl UnitsReader {
+	fn peek(&self) -> Option<char> {
+		self.chars.get(self.position).copied()
+	}
+
+	fn next_if(&mut self, predicate: impl FnOnce(char) -> bool) -> Option<char> {
+		let c = self.peek().filter(|&c| predicate(c))?;
+		self.position += 1;
+		Some(c)
+	}
+
+	fn skip_whitespace(&mut self) {
+		while self.next_if(char::is_whitespace).is_some() {}
+	}
+
+	fn unexpected(&self) -> ParseQuantityError {
+		match self.peek() {
+			Some(found) => ParseQuantityError::UnexpectedChar {
+				found,
+				position: self.position,
+			},
+			None => ParseQuantityError::UnexpectedEnd {
+				position: self.position,
+			},
+		}
+	}
+
+	/// Reads units up to the end of the string or a closing parenthesis
+	fn read_product(&mut self) -> Result<Units, ParseQuantityError> {
+		let mut result = Units::new();
+		let mut first = true;
+		loop {
+			self.skip_whitespace();
+			if matches!(self.peek(), None | Some(')')) {
+				return Ok(result);
+			}
+			if !first && self.next_if(|c| c == '*' || c == '·').is_some() {
+				self.skip_whitespace();
+			}
+			let dividing = self.next_if(|c| c == '/').is_some();
+			self.skip_whitespace();
+			first = false;
+
+			let factor = if self.next_if(|c| c == '(').is_some() {
+				let group = self.read_product()?;
+				if group.is_dimensionless() || self.next_if(|c| c == ')').is_none() {
+					return Err(self.unexpected());
+				}
+				group
+			} else {
+				let start = self.position;
+				while self.next_if(char::is_alphabetic).is_some() {}
+				if self.position == start {
+					return Err(self.unexpected());
+				}
+				Units::of(&self.chars[start..self.position].iter().collect::<String>())
+			};
+
+			let mut power = 1;
+			if self.next_if(|c| c == '^').is_some() {
+				let sign = if self.next_if(|c| c == '-').is_some() {
+					-1
+				} else {
+					1
+				};
+				let start = self.position;
+				while self.next_if(|c| c.is_ascii_digit()).is_some() {}
+				let digits: String = self.chars[start..self.position].iter().collect();
+				power = sign * digits.parse::<Num>().map_err(|_| self.unexpected())?;
+			}
+			if dividing {
+				power = -power;
+			}
+			result = factor
+				.checked_powi(power)
+				.and_then(|factor| result.checked_mul(factor))
+				.ok_or(ParseQuantityError::PowerOverflow {
+					position: self.position,
+				})?;
+		}
+	}
+}
+
+/// A measurement along with its units, e.g. `12.5 g/mol`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quantity {
+	value: RealNumber,
+	units: Units,
+}
+
+impl Quantity {
+	pub fn new(value: RealNumber, units: Units) -> Self {
+		Self { value, units }
+	}
+
+	pub fn value(&self) -> &RealNumber {
+		&self.value
+	}
+
+	pub fn units(&self) -> &Units {
+		&self.units
+	}
+
+	/// Raises the quantity to the power
+	pub fn checked_powi(self, power: Num) -> Result<Self, QuantityArithmeticError> {
+		let overflow = || QuantityArithmeticError::Overflow;
+		Ok(Self::new(
+			self.value.checked_powi(power).ok_or_else(overflow)?,
+			self.units.checked_powi(power).ok_or_else(overflow)?,
+		))
+	}
+
+	pub fn checked_mul(self, rhs: Self) -> Result<Self, QuantityArithmeticError> {
+		let overflow = || QuantityArithmeticError::Overflow;
+		Ok(Self::new(
+			self.value.checked_mul(rhs.value).ok_or_else(overflow)?,
+			self.units.checked_mul(rhs.units).ok_or_else(overflow)?,
+		))
+	}
+
+	pub fn checked_div(self, rhs: Self) -> Result<Self, QuantityArithmeticError> {
+		let overflow = || QuantityArithmeticError::Overflow;
+		Ok(Self::new(
+			self.value.checked_div(rhs.value).ok_or_else(overflow)?,
+			self.units.checked_div(rhs.units).ok_or_else(overflow)?,
+		))
+	}
+
+	/// Adds quantities with the same units
+	pub fn checked_add(self, rhs: Self) -> Result<Self, QuantityArithmeticError> {
+		if self.units != rhs.units {
+			return Err(QuantityArithmeticError::IncompatibleUnits(
+				IncompatibleUnitsError {
+					lhs: self.units,
+					rhs: rhs.units,
+				},
+			));
+		}
+		let value = self
+			.value
+			.checked_add(rhs.value)
+			.ok_or(QuantityArithmeticError::Overflow)?;
+		Ok(Self::new(value, self.units))
+	}
+
+	/// Subtracts quantities with the same units
+	pub fn checked_sub(self, rhs: Self) -> Result<Self, QuantityArithmeticError> {
+		self.checked_add(Self::new(-rhs.value, rhs.units))
+	}
+}
+
+/// A number is a dimensionless quantity
+impl From<RealNumber> for Quantity {
+	fn from(value: RealNumber) -> Self {
+		Self::new(value, Units::new())
+	}
+}
+
+impl Display for Quantity {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}", self.value)?;
+		if !self.units.is_dimensionless() {
+			write!(f, " {}", self.units)?;
+		}
+		Ok(())
+	}
+}
+
+/// Parses a number followed by its units, e.g. `6.02e23 mol^-1`. Without units the quantity is
+/// dimensionless.
+impl FromStr for Quantity {
+	type Err = ParseQuantityError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let chars: Vec<char> = s.chars().collect();
+		let start = chars.iter().take_while(|c| c.is_whitespace()).count();
+
+		// the number ends at the first char that can't continue it; an `e` continues it only as
+		// the start of an exponent, so that `2 eV` and `2e3 eV` are both read correctly
+		let mut end = start;
+		while let Some(&c) = chars.get(end) {
+			let is_exponent = (c == 'e' || c == 'E')
+				&& match chars.get(end + 1) {
+					Some(next) if next.is_ascii_digit() => true,
+					Some('+' | '-') => chars.get(end + 2).is_some_and(char::is_ascii_digit),
+					_ => false,
+				};
+			let is_sign = (c == '+' || c == '-') && end == start;
+			if is_exponent {
+				end += 2;
+			} else if c.is_ascii_digit() || c == '.' || is_sign {
+				end += 1;
+			} else {
+				break;
+			}
+		}
+
+		let literal: String = chars[start..end].iter().collect();
+		let value: RealNumber =
+			literal
+				.parse()
+				.map_err(|error| ParseQuantityError::MalformedValue {
+					error,
+					position: start,
+				})?;
+		let units: Units = chars[end..]
+			.iter()
+			.collect::<String>()
+			.parse()
+			.map_err(|error: ParseQuantityError| error.offset(end))?;
+		Ok(Self::new(value, units))
+	}
+}
+
+/// An error in a quantity or its units; each position is counted in chars from the start of the
+/// string
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseQuantityError {
+	MalformedValue {
+		error: ParseRealNumberError,
+		position: usize,
+	},
+	UnexpectedChar {
+		found: char,
+		position: usize,
+	},
+	UnexpectedEnd {
+		position: usize,
+	},
+	/// A power of a unit is too large, e.g. from `m^2000000000*m^2000000000`
+	PowerOverflow {
+		position: usize,
+	},
+}
+impl ParseQuantityError {
+	pub fn position(&self) -> usize {
+		match self {
+			ParseQuantityError::MalformedValue { position, .. }
+			| ParseQuantityError::UnexpectedChar { position, .. }
+			| ParseQuantityError::UnexpectedEnd { position }
+			| ParseQuantityError::PowerOverflow { position } => *position,
+		}
+	}
+
+	fn offset(mut self, offset: usize) -> Self {
+		match &mut self {
+			ParseQuantityError::MalformedValue { position, .. }
+			| ParseQuantityError::UnexpectedChar { position, .. }
+			| ParseQuantityError::UnexpectedEnd { position }
+			| ParseQuantityError::PowerOverflow { position } => *position += offset,
+		}
+		self
+	}
+}
+impl Display for ParseQuantityError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			ParseQuantityError::MalformedValue { error, .. } => write!(f, "{}", error),
+			ParseQuantityError::UnexpectedChar { found, .. } => {
+				write!(f, "Unexpected '{}' in units", found)
+			},
+			ParseQuantityError::UnexpectedEnd { .. } => write!(f, "Unexpected end of units"),
+			ParseQuantityError::PowerOverflow { .. } => write!(f, "Power of units is too large"),
+		}
+	}
+}
+impl Error for ParseQuantityError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IncompatibleUnitsError {
+	pub lhs: Units,
+	pub rhs: Units,
+}
+impl Display for IncompatibleUnitsError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let name = |units: &Units| {
+			if units.is_dimensionless() {
+				"no units".to_string()
+			} else {
+				format!("'{}'", units)
+			}
+		};
+		write!(
+			f,
+			"Cannot combine {} with {}",
+			name(&self.lhs),
+			name(&self.rhs)
+		)
+	}
+}
+impl Error for IncompatibleUnitsError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum QuantityArithmeticError {
+	IncompatibleUnits(IncompatibleUnitsError),
+	/// The value or a power of the units is too large to store
+	Overflow,
+}
+impl Display for QuantityArithmeticError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			QuantityArithmeticError::IncompatibleUnits(err) => Display::fmt(err, f),
+			QuantityArithmeticError::Overflow => {
+				write!(f, "A value or power of units is too large")
+			},
+		}
+	}
+}
+impl Error for QuantityArithmeticError {}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn quantity(s: &str) -> Quantity {
+		s.parse().unwrap()
+	}
+
+	#[test]
+	fn parses_and_displays_units() {
+		let cases = [
+			("12.5 g/mol", "12.5 g/mol"),
+			("9.81 m s^-2", "9.81 m/s^2"),
+			("8.314 J/(mol*K)", "8.314 J/mol/K"),
+			("6.02e23 mol^-1", "6.02e23 mol^-1"),
+			("2.0e3 eV", "2.0e3 eV"),
+			("-40. mL", "-40. mL"),
+			("3.0", "3.0"),
+		];
+		for (input, output) in cases {
+			assert_eq!(quantity(input).to_string(), output, "{}", input);
+		}
+		assert_eq!(
+			quantity("1 kg*m^2/s^2").units(),
+			&"kg m^2 s^-2".parse().unwrap()
+		);
+	}
+
+	#[test]
+	fn combines_units() {
+		let molar_mass = quantity("18.02 g/mol");
+		let amount = quantity("2.000 mol");
+		assert_eq!(
+			molar_mass.clone().checked_mul(amount).unwrap().to_string(),
+			"36.04 g"
+		);
+		assert_eq!(
+			molar_mass
+				.clone()
+				.checked_div(quantity("2 g/mol"))
+				.unwrap()
+				.units(),
+			&Units::new()
+		);
+		assert_eq!(
+			quantity("2.0 m").checked_powi(2).unwrap().to_string(),
+			"4.0 m^2"
+		);
+
+		// values and powers of units that overflow are rejected rather than wrapping or panicking
+		let overflow = Err(QuantityArithmeticError::Overflow);
+		let huge = || quantity("1 m^2000000000");
+		assert_eq!(huge().checked_mul(huge()), overflow);
+		assert_eq!(huge().checked_powi(2), overflow);
+		assert_eq!(quantity("1 m^-2147483647").checked_div(huge()), overflow);
+		assert_eq!(
+			quantity("1.0e300 g").checked_mul(quantity("1.0e300 g")),
+			overflow
+		);
+		assert_eq!(
+			quantity("1.0e308 g").checked_add(quantity("1.0e308 g")),
+			overflow
+		);
+
+		assert_eq!(
+			quantity("1.5 g").checked_add(quantity("0.25 g")),
+			Ok(quantity("1.8 g"))
+		);
+		assert_eq!(
+			quantity("1.5 g").checked_sub(molar_mass),
+			Err(QuantityArithmeticError::IncompatibleUnits(
+				IncompatibleUnitsError {
+					lhs: Units::of("g"),
+					rhs: "g/mol".parse().unwrap()
+				}
+			))
+		);
+	}
+
+	#[test]
+	fn reports_errors() {
+		assert_eq!(
+			"1.0 g/".parse::<Quantity>(),
+			Err(ParseQuantityError::UnexpectedEnd { position: 6 })
+		);
+		assert_eq!(
+			"1.0 g^x".parse::<Quantity>(),
+			Err(ParseQuantityError::UnexpectedChar {
+				found: 'x',
+				position: 6
+			})
+		);
+		assert_eq!("g".parse::<Quantity>().unwrap_err().position(), 0);
+		assert_eq!(
+			"1 m^2000000000 m^2000000000".parse::<Quantity>(),
+			Err(ParseQuantityError::PowerOverflow { position: 27 })
+		);
+	}
+}
@@ -1,10 +1,510 @@
-use std::fmt::Display;
+use std::{error::Error, fmt::Display, ops::Neg, str::FromStr};
 
 /// A real number with significant figures.
 #[derive(Debug, PartialEq, Clone)]
-pub struct RealNumber {}
+pub struct RealNumber {
+	// normalized so that 1 <= |mantissa| < 10 (unless the number is zero) and rounded to the
+	// number of significant figures
+	mantissa: f64,
+	exponent: i32,
+	// None means that the number is exact, i.e. has infinitely many significant figures
+	sig_figs: Option<u32>,
+	// the standard uncertainty, if known, which is propagated through arithmetic assuming that the
+	// operands are independent
+	uncertainty: Option<f64>,
+}
+
+impl RealNumber {
+	/// Creates a measured number, rounding the value to the specified number of significant
+	/// figures.
+	pub fn new(value: f64, sig_figs: u32) -> Self {
+		let sig_figs = sig_figs.max(1);
+		let (mantissa, exponent) = normalize(value, Some(sig_figs));
+		Self {
+			mantissa,
+			exponent,
+			sig_figs: Some(sig_figs),
+			uncertainty: None,
+		}
+	}
+
+	/// Creates an exact number, such as a count or a defined constant.
+	pub fn exact(value: f64) -> Self {
+		let (mantissa, exponent) = normalize(value, None);
+		Self {
+			mantissa,
+			exponent,
+			sig_figs: None,
+			uncertainty: None,
+		}
+	}
+
+	/// Attaches a standard uncertainty to the number, e.g. 0.002 for 12.011 ± 0.002
+	pub fn with_uncertainty(mut self, uncertainty: f64) -> Self {
+		self.uncertainty = Some(uncertainty.abs());
+		self
+	}
+
+	/// Creates a measured number whose last significant digit is in the specified decimal place
+	/// (e.g. -2 for hundredths), as is the result of adding or subtracting measurements.
+	fn with_last_digit_place(value: f64, place: i32) -> Self {
+		let (mantissa, exponent) = normalize(value, None);
+		// the number of digits from the first significant one down to the place, which is zero if
+		// the value is below the place but may still round up to one in it
+		let digits = i64::from(exponent) - i64::from(place) + 1;
+		if value == 0.0 || digits < 0 || (digits == 0 && mantissa.abs() < 5.0) {
+			// zero still keeps track of its precision, e.g. 1.20 - 1.20 = 0.00
+			return Self {
+				mantissa: 0.0,
+				exponent: place,
+				sig_figs: Some(1),
+				uncertainty: None,
+			};
+		}
+		if digits == 0 {
+			return Self {
+				mantissa: mantissa.signum(),
+				exponent: place,
+				sig_figs: Some(1),
+				uncertainty: None,
+			};
+		}
+		let sig_figs = u32::try_from(digits).unwrap_or(u32::MAX);
+		let result = Self::new(value, sig_figs);
+		// rounding may carry into another digit, which is significant too, e.g. 9.96 to tenths
+		if result.exponent > exponent {
+			Self::new(value, sig_figs.saturating_add(1))
+		} else {
+			result
+		}
+	}
+
+	pub fn value(&self) -> f64 {
+		scale(self.mantissa, self.exponent)
+	}
+
+	pub fn mantissa(&self) -> f64 {
+		self.mantissa
+	}
+
+	pub fn exponent(&self) -> i32 {
+		self.exponent
+	}
+
+	/// Returns the number of significant figures, or None if the number is exact.
+	pub fn sig_figs(&self) -> Option<u32> {
+		self.sig_figs
+	}
+
+	pub fn is_exact(&self) -> bool {
+		self.sig_figs.is_none()
+	}
+
+	/// Returns the standard uncertainty, or None if it isn't known
+	pub fn uncertainty(&self) -> Option<f64> {
+		self.uncertainty
+	}
+
+	/// The uncertainty relative to the magnitude of the number
+	fn relative_uncertainty(&self) -> Option<f64> {
+		self.uncertainty
+			.map(|uncertainty| uncertainty / self.value().abs())
+	}
+
+	/// Returns the decimal place of the last significant digit (e.g. -2 for hundredths), or None
+	/// if the number is exact.
+	pub fn last_digit_place(&self) -> Option<i32> {
+		self.sig_figs
+			.map(|sig_figs| self.exponent - (sig_figs as i32 - 1))
+	}
+
+	/// The sum is only as precise as the least precise decimal place of the addends. Returns None
+	/// if the sum is too large to store.
+	pub fn checked_add(self, rhs: Self) -> Option<Self> {
+		let value = finite(self.value() + rhs.value())?;
+		let result = match self.last_digit_place().max(rhs.last_digit_place()) {
+			None => Self::exact(value),
+			Some(place) => Self::with_last_digit_place(value, place),
+		};
+		Some(
+			match combine_uncertainties(self.uncertainty, rhs.uncertainty) {
+				None => result,
+				Some(uncertainty) => result.with_uncertainty(uncertainty),
+			},
+		)
+	}
+
+	/// Returns None if the difference is too large to store
+	pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+		self.checked_add(-rhs)
+	}
+
+	/// The product has as many significant figures as the factor with the fewest. Returns None if
+	/// the product is too large to store.
+	pub fn checked_mul(self, rhs: Self) -> Option<Self> {
+		let value = finite(self.value() * rhs.value())?;
+		let result = match min_sig_figs(self.sig_figs, rhs.sig_figs) {
+			None => Self::exact(value),
+			Some(sig_figs) => Self::new(value, sig_figs),
+		};
+		let relative =
+			combine_uncertainties(self.relative_uncertainty(), rhs.relative_uncertainty());
+		Some(result.with_relative_uncertainty(value, relative))
+	}
+
+	/// The quotient has as many significant figures as the operand with the fewest. Returns None if
+	/// the quotient is too large to store or the divisor is zero.
+	pub fn checked_div(self, rhs: Self) -> Option<Self> {
+		let value = finite(self.value() / rhs.value())?;
+		let result = match min_sig_figs(self.sig_figs, rhs.sig_figs) {
+			None => Self::exact(value),
+			Some(sig_figs) => Self::new(value, sig_figs),
+		};
+		let relative =
+			combine_uncertainties(self.relative_uncertainty(), rhs.relative_uncertainty());
+		Some(result.with_relative_uncertainty(value, relative))
+	}
+
+	/// Raises the number to an exact integer power, which (like repeated multiplication) keeps the
+	/// number of significant figures. Returns None if the result is too large to store, including
+	/// when zero is raised to a negative power.
+	pub fn checked_powi(self, power: i32) -> Option<Self> {
+		let value = finite(self.value().powi(power))?;
+		let result = match self.sig_figs {
+			None => Self::exact(value),
+			Some(sig_figs) => Self::new(value, sig_figs),
+		};
+		let relative = self
+			.relative_uncertainty()
+			.map(|relative| relative * f64::from(power.abs()));
+		Some(result.with_relative_uncertainty(value, relative))
+	}
+
+	/// Raises the number to a possibly measured power, keeping the fewer significant figures of
+	/// the two. Returns None if the result is too large to store or isn't a real number, e.g. for
+	/// a negative number raised to a fractional power.
+	pub fn checked_pow(self, power: Self) -> Option<Self> {
+		let value = finite(self.value().powf(power.value()))?;
+		let result = match min_sig_figs(self.sig_figs, power.sig_figs) {
+			None => Self::exact(value),
+			Some(sig_figs) => Self::new(value, sig_figs),
+		};
+		// the relative uncertainty of a^b is b * (ua / a) from the base and ln(a) * ub from the power
+		let relative = combine_uncertainties(
+			self.relative_uncertainty()
+				.map(|relative| relative * power.value().abs()),
+			power
+				.uncertainty
+				.map(|uncertainty| uncertainty * self.value().abs().ln().abs()),
+		);
+		Some(result.with_relative_uncertainty(value, relative))
+	}
+
+	/// Attaches an uncertainty relative to the unrounded value of the number
+	fn with_relative_uncertainty(self, unrounded: f64, relative: Option<f64>) -> Self {
+		match relative {
+			None => self,
+			Some(relative) => self.with_uncertainty(relative * unrounded.abs()),
+		}
+	}
+}
+
+/// Combines independent uncertainties in quadrature
+fn combine_uncertainties(a: Option<f64>, b: Option<f64>) -> Option<f64> {
+	match (a, b) {
+		(Some(a), Some(b)) => Some(a.hypot(b)),
+		(a, None) => a,
+		(None, b) => b,
+	}
+}
+
+/// Returns the value if it is neither infinite nor NaN
+fn finite(value: f64) -> Option<f64> {
+	value.is_finite().then_some(value)
+}
+
+/// Multiplies the value by 10^exponent in two steps, since 10^exponent alone overflows or underflows
+/// for the exponents of the largest and smallest (subnormal) values
+fn scale(value: f64, exponent: i32) -> f64 {
+	let half = exponent / 2;
+	value * 10f64.powi(half) * 10f64.powi(exponent - half)
+}
+
+/// Splits the value into a mantissa and exponent, rounding the mantissa to the number of
+/// significant figures if there is one.
+fn normalize(value: f64, sig_figs: Option<u32>) -> (f64, i32) {
+	if value == 0.0 || !value.is_finite() {
+		return (value, 0);
+	}
+
+	let mut exponent = value.abs().log10().floor() as i32;
+	let mut mantissa = scale(value, -exponent);
+	// log10 isn't exact, so the mantissa may be slightly out of range
+	if mantissa.abs() >= 10.0 {
+		mantissa /= 10.0;
+		exponent += 1;
+	} else if mantissa.abs() < 1.0 {
+		mantissa *= 10.0;
+		exponent -= 1;
+	}
+
+	// an f64 holds no more than 17 significant digits, so rounding to more would change nothing
+	if let Some(sig_figs) = sig_figs.filter(|&sig_figs| sig_figs <= 17) {
+		let scale = 10f64.powi(sig_figs as i32 - 1);
+		mantissa = (mantissa * scale).round() / scale;
+		// rounding may carry into another digit, e.g. 9.99 to 2 sig figs
+		if mantissa.abs() >= 10.0 {
+			mantissa /= 10.0;
+			exponent += 1;
+		}
+	}
+	(mantissa, exponent)
+}
+
+impl Neg for RealNumber {
+	type Output = Self;
+
+	fn neg(mut self) -> Self::Output {
+		self.mantissa = -self.mantissa;
+		self
+	}
+}
+
+fn min_sig_figs(a: Option<u32>, b: Option<u32>) -> Option<u32> {
+	match (a, b) {
+		(Some(a), Some(b)) => Some(a.min(b)),
+		(a, None) => a,
+		(None, b) => b,
+	}
+}
+
+/// Displays the number with exactly its significant figures, switching to scientific notation
+/// when plain notation would be ambiguous (e.g. `1.20e3` rather than `1200`) or very small. A
+/// trailing decimal point marks trailing zeros as significant, e.g. `100.`
 impl Display for RealNumber {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		todo!();
+		let Some(sig_figs) = self.sig_figs else {
+			return write!(f, "{}", self.value());
+		};
+		let sig_figs = i32::try_from(sig_figs).unwrap_or(i32::MAX);
+
+		if self.exponent >= sig_figs || self.exponent < -3 {
+			write!(
+				f,
+				"{:.*}e{}",
+				(sig_figs - 1) as usize,
+				self.mantissa,
+				self.exponent
+			)
+		} else {
+			let decimals = (sig_figs - 1 - self.exponent).max(0) as usize;
+			let plain = format!("{:.*}", decimals, self.value());
+			if decimals == 0 && plain.ends_with('0') {
+				write!(f, "{}.", plain)
+			} else {
+				write!(f, "{}", plain)
+			}
+		}
+	}
+}
+
+/// Parses a decimal literal such as `56.4e2` or `6.02e-23`. Leading zeros are not significant;
+/// trailing zeros are significant only if there is a decimal point.
+impl FromStr for RealNumber {
+	type Err = ParseRealNumberError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let error = || ParseRealNumberError::new(s);
+
+		let (significand, power) = match s.split_once(['e', 'E']) {
+			None => (s, 0),
+			Some((significand, power)) => (significand, power.parse::<i32>().map_err(|_| error())?),
+		};
+		let unsigned = significand.strip_prefix(['+', '-']).unwrap_or(significand);
+		let (int_part, frac_part, has_point) = match unsigned.split_once('.') {
+			None => (unsigned, "", false),
+			Some((int_part, frac_part)) => (int_part, frac_part, true),
+		};
+		let digits = format!("{}{}", int_part, frac_part);
+		if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+			return Err(error());
+		}
+		let value: f64 = s.parse().map_err(|_| error())?;
+		if !value.is_finite() {
+			return Err(error());
+		}
+
+		let Some(first_sig_digit) = digits.find(|c| c != '0') else {
+			// all zeros; the precision is given by the last written digit
+			let place = i32::try_from(frac_part.len())
+				.ok()
+				.and_then(|decimals| power.checked_sub(decimals))
+				.ok_or_else(error)?;
+			return Ok(Self::with_last_digit_place(0.0, place));
+		};
+		if value == 0.0 {
+			// the literal is too small to store, e.g. `1.0e-400`
+			return Err(error());
+		}
+		let last_sig_digit = if has_point {
+			digits.len()
+		} else {
+			digits.trim_end_matches('0').len()
+		};
+		Ok(Self::new(value, (last_sig_digit - first_sig_digit) as u32))
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseRealNumberError {
+	literal: String,
+}
+impl ParseRealNumberError {
+	pub(crate) fn new(literal: &str) -> Self {
+		Self {
+			literal: literal.to_string(),
+		}
+	}
+}
+impl Display for ParseRealNumberError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "'{}' is not a valid real number", self.literal)
+	}
+}
+impl Error for ParseRealNumberError {}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn real(s: &str) -> RealNumber {
+		s.parse().unwrap()
+	}
+
+	#[test]
+	fn parses_sig_figs() {
+		let cases = [
+			("56.4e2", 3, 3),
+			("1.234", 4, 0),
+			("0.00120", 3, -3),
+			("100.", 3, 2),
+			("100", 1, 2),
+			("6.02e-23", 3, -23),
+			("-4.50", 3, 0),
+		];
+		for (literal, sig_figs, exponent) in cases {
+			let number = real(literal);
+			assert_eq!(number.sig_figs(), Some(sig_figs), "{}", literal);
+			assert_eq!(number.exponent(), exponent, "{}", literal);
+		}
+		assert_eq!(real("0.00").last_digit_place(), Some(-2));
+		assert!("1.2.3".parse::<RealNumber>().is_err());
+		assert!("1.2e".parse::<RealNumber>().is_err());
+		// too large or small to store
+		assert!("1.0e400".parse::<RealNumber>().is_err());
+		assert!("-1.0e400".parse::<RealNumber>().is_err());
+		assert!("1.0e-400".parse::<RealNumber>().is_err());
+		assert_eq!(real("0.0e-400").to_string(), "0e-401");
+	}
+
+	#[test]
+	fn applies_sig_fig_rules() {
+		let add = |a: &str, b: &str| real(a).checked_add(real(b)).unwrap().to_string();
+		let mul = |a: &str, b: &str| real(a).checked_mul(real(b)).unwrap().to_string();
+		assert_eq!(
+			real("12.11")
+				.checked_add(real("18.0"))
+				.and_then(|sum| sum.checked_add(real("1.013")))
+				.unwrap()
+				.to_string(),
+			"31.1"
+		);
+		assert_eq!(
+			real("1.20").checked_sub(real("1.20")).unwrap().to_string(),
+			"0.00"
+		);
+		assert_eq!(add("9.96", "0.1"), "10.1");
+		assert_eq!(add("9.96", "0.04"), "10.00");
+		assert_eq!(add("0.6", "1"), "2");
+		assert_eq!(mul("4.56", "1.4"), "6.4");
+		assert_eq!(
+			real("8.315").checked_div(real("298.")).unwrap().to_string(),
+			"0.0279"
+		);
+		assert_eq!(
+			real("2.0")
+				.checked_mul(RealNumber::exact(3.0))
+				.unwrap()
+				.to_string(),
+			"6.0"
+		);
+		assert_eq!(mul("150.0", "10."), "1.5e3");
+	}
+
+	#[test]
+	fn rejects_results_that_are_not_finite() {
+		assert_eq!(real("1.0e308").checked_add(real("1.0e308")), None);
+		assert_eq!(real("-1.0e308").checked_sub(real("1.0e308")), None);
+		assert_eq!(real("1.0e300").checked_mul(real("1.0e300")), None);
+		assert_eq!(real("1.0").checked_div(real("0.0")), None);
+		assert_eq!(real("2.0").checked_powi(2000), None);
+		assert_eq!(RealNumber::exact(0.0).checked_powi(-1), None);
+		assert_eq!(real("-4.0").checked_pow(real("0.5")), None);
+		assert_eq!(
+			real("1.0e308")
+				.checked_add(real("-1.0e308"))
+				.unwrap()
+				.to_string(),
+			"0e307"
+		);
+	}
+
+	#[test]
+	fn handles_subnormal_values() {
+		let tiny = real("1.0e-320");
+		assert_eq!(tiny.exponent(), -320);
+		assert_eq!(tiny.to_string(), "1.0e-320");
+		assert_eq!(
+			tiny.clone().checked_mul(real("2.0")).unwrap().to_string(),
+			"2.0e-320"
+		);
+		assert!((tiny.value() / 1.0e-320 - 1.0).abs() < 1e-3);
+		assert_eq!(RealNumber::new(4.94e-324, 3).exponent(), -324);
+	}
+
+	#[test]
+	fn propagates_uncertainty() {
+		let a = real("12.011").with_uncertainty(0.002);
+		let b = real("1.008").with_uncertainty(0.0002);
+		let sum = a.clone().checked_add(b.clone()).unwrap();
+		assert!((sum.uncertainty().unwrap() - 0.002f64.hypot(0.0002)).abs() < 1e-12);
+		let scaled = a.clone().checked_mul(RealNumber::exact(3.0)).unwrap();
+		assert!((scaled.uncertainty().unwrap() - 0.006).abs() < 1e-12);
+		let quotient = a.checked_div(real("2.000")).unwrap();
+		assert!((quotient.uncertainty().unwrap() - 0.001).abs() < 1e-12);
+		assert_eq!(b.checked_sub(real("1.000")).unwrap().to_string(), "0.008");
+		assert_eq!(
+			real("1.0").checked_add(real("2.0")).unwrap().uncertainty(),
+			None
+		);
+	}
+
+	#[test]
+	fn displays_correctly() {
+		let cases = [
+			("56.4e2", "5.64e3"),
+			("1.234", "1.234"),
+			("0.00120", "0.00120"),
+			("0.0000120", "1.20e-5"),
+			("100.", "100."),
+			("1.00e2", "100."),
+			("1.0e2", "1.0e2"),
+			("9.999e2", "999.9"),
+			("-4.50", "-4.50"),
+		];
+		for (literal, displayed) in cases {
+			assert_eq!(real(literal).to_string(), displayed);
+		}
+		assert_eq!(RealNumber::new(0.0996, 2).to_string(), "0.10");
 	}
 }
@@ -0,0 +1,533 @@
+use std::{collections::HashMap, error::Error, fmt::Display, str::FromStr};
+
+/// A molecule written in SMILES notation, e.g. `CC(=O)O` for acetic acid. Only what determines the
+/// molecular formula is kept; stereochemistry (`@`, `/`, `\`) and atom classes are read but
+/// ignored.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Smiles {
+	atoms: Vec<SmilesAtom>,
+}
+
+/// A heavy atom of a SMILES string along with the hydrogens attached to it
+#[derive(Debug, Clone, PartialEq)]
+pub struct SmilesAtom {
+	/// The element symbol, capitalized even if the atom was written as aromatic, e.g. `C` for `c`
+	pub symbol: String,
+	pub mass_number: Option<u32>,
+	/// The explicit hydrogen count of a bracket atom, or the implicit hydrogens of any other atom
+	pub hydrogens: u32,
+	pub charge: i32,
+}
+
+impl Smiles {
+	/// The atoms in the order they were written
+	pub fn atoms(&self) -> &[SmilesAtom] {
+		&self.atoms
+	}
+}
+
+/// The usual valences of the elements that may be written outside of brackets, lowest first. Such
+/// an atom gets enough implicit hydrogens to reach the lowest valence that its bonds don't exceed.
+static ORGANIC_SUBSET: &[(&str, &[u32])] = &[
+	("B", &[3]),
+	("C", &[4]),
+	("N", &[3, 5]),
+	("O", &[2]),
+	("P", &[3, 5]),
+	("S", &[2, 4, 6]),
+	("F", &[1]),
+	("Cl", &[1]),
+	("Br", &[1]),
+	("I", &[1]),
+];
+
+/// The elements that may be written in lowercase to mark them as aromatic
+static AROMATIC: &[&str] = &["b", "c", "n", "o", "p", "s", "se", "as"];
+
+/// An atom being read along with what is needed to work out its implicit hydrogens
+struct AtomState {
+	atom: SmilesAtom,
+	in_brackets: bool,
+	aromatic: bool,
+	bond_orders: u32,
+}
+
+struct SmilesReader {
+	chars: Vec<char>,
+	position: usize,
+	atoms: Vec<AtomState>,
+	// the atom that the next atom bonds to, if any
+	previous: Option<usize>,
+	// the bond written before the next atom or ring closure, along with where it was written
+	pending_bond: Option<(u32, usize)>,
+	// the atom that each open branch started from
+	branches: Vec<(Option<usize>, usize)>,
+	// the atom, bond and position of each ring bond that hasn't been closed yet
+	open_rings: HashMap<u32, (usize, Option<u32>, usize)>,
+}
+
+impl SmilesReader {
+	fn new(source: &str) -> Self {
+		Self {
+			chars: source.chars().collect(),
+			position: 0,
+			atoms: Vec::new(),
+			previous: None,
+			pending_bond: None,
+			branches: Vec::new(),
+			open_rings: HashMap::new(),
+		}
+	}
+
+	fn peek(&self) -> Option<char> {
+		self.chars.get(self.position).copied()
+	}
+
+	fn next_if(&mut self, predicate: impl FnOnce(char) -> bool) -> Option<char> {
+		let c = self.peek().filter(|&c| predicate(c))?;
+		self.position += 1;
+		Some(c)
+	}
+
+	fn unexpected(&self) -> ParseSmilesError {
+		match self.peek() {
+			Some(found) => ParseSmilesError::UnexpectedChar {
+				found,
+				position: self.position,
+			},
+			None => ParseSmilesError::UnexpectedEnd {
+				position: self.position,
+			},
+		}
+	}
+
+	fn read_number(&mut self) -> Option<u32> {
+		let start = self.position;
+		while self.next_if(|c| c.is_ascii_digit()).is_some() {}
+		let digits: String = self.chars[start..self.position].iter().collect();
+		digits.parse().ok()
+	}
+
+	fn read(mut self) -> Result<Smiles, ParseSmilesError> {
+		while let Some(c) = self.peek() {
+			match c {
+				'[' | 'A'..='Z' | 'a'..='z' => {
+					let atom = if c == '[' {
+						self.read_bracket_atom()?
+					} else {
+						self.read_organic_atom()?
+					};
+					self.atoms.push(atom);
+					let index = self.atoms.len() - 1;
+					if let Some(previous) = self.previous {
+						let order = self.pending_bond.take().map_or(1, |(order, _)| order);
+						self.bond(previous, index, order);
+					}
+					self.previous = Some(index);
+				},
+				'-' | '=' | '#' | '$' | ':' | '/' | '\\' => {
+					if self.previous.is_none() || self.pending_bond.is_some() {
+						return Err(self.unexpected());
+					}
+					let order = match c {
+						'=' => 2,
+						'#' => 3,
+						'$' => 4,
+						_ => 1,
+					};
+					self.pending_bond = Some((order, self.position));
+					self.position += 1;
+				},
+				'(' => {
+					if self.previous.is_none() || self.pending_bond.is_some() {
+						return Err(self.unexpected());
+					}
+					self.branches.push((self.previous, self.position));
+					self.position += 1;
+				},
+				')' => {
+					let Some((branch_start, _)) = self.branches.pop() else {
+						return Err(self.unexpected());
+					};
+					if self.pending_bond.is_some() {
+						return Err(self.unexpected());
+					}
+					self.previous = branch_start;
+					self.position += 1;
+				},
+				'0'..='9' | '%' => self.read_ring_bond()?,
+				'.' => {
+					if self.pending_bond.is_some() {
+						return Err(self.unexpected());
+					}
+					self.previous = None;
+					self.position += 1;
+				},
+				_ => return Err(self.unexpected()),
+			}
+		}
+
+		if let Some((_, position)) = self.pending_bond {
+			return Err(ParseSmilesError::UnexpectedEnd { position });
+		}
+		if let Some(&(_, position)) = self.branches.last() {
+			return Err(ParseSmilesError::UnclosedBranch { position });
+		}
+		if let Some((&label, &(_, _, position))) = self
+			.open_rings
+			.iter()
+			.min_by_key(|(_, (_, _, position))| *position)
+		{
+			return Err(ParseSmilesError::UnclosedRing { label, position });
+		}
+		if self.atoms.is_empty() {
+			return Err(self.unexpected());
+		}
+
+		let atoms = self
+			.atoms
+			.into_iter()
+			.map(|mut state| {
+				if !state.in_brackets {
+					state.atom.hydrogens =
+						implicit_hydrogens(&state.atom.symbol, state.bond_orders, state.aromatic);
+				}
+				state.atom
+			})
+			.collect();
+		Ok(Smiles { atoms })
+	}
+
+	fn bond(&mut self, a: usize, b: usize, order: u32) {
+		self.atoms[a].bond_orders += order;
+		self.atoms[b].bond_orders += order;
+	}
+
+	/// Reads an atom of the organic subset, e.g. `Cl` or `c`
+	fn read_organic_atom(&mut self) -> Result<AtomState, ParseSmilesError> {
+		let rest: String = self.chars[self.position..].iter().take(2).collect();
+		let aromatic = rest.starts_with(|c: char| c.is_ascii_lowercase());
+		let symbol = if aromatic {
+			AROMATIC
+				.iter()
+				.find(|&&symbol| symbol.len() == 1 && rest.starts_with(symbol))
+		} else {
+			// prefer two letters so that `Cl` isn't read as `C` followed by `l`
+			ORGANIC_SUBSET
+				.iter()
+				.map(|(symbol, _)| symbol)
+				.filter(|&&symbol| rest.starts_with(symbol))
+				.max_by_key(|symbol| symbol.len())
+		};
+		let Some(symbol) = symbol else {
+			return Err(self.unexpected());
+		};
+		self.position += symbol.len();
+		Ok(AtomState {
+			atom: SmilesAtom {
+				symbol: capitalize(symbol),
+				mass_number: None,
+				hydrogens: 0,
+				charge: 0,
+			},
+			in_brackets: false,
+			aromatic,
+			bond_orders: 0,
+		})
+	}
+
+	/// Reads an atom in brackets, e.g. `[13CH4]` or `[NH4+]` or `[Fe+3]`
+	fn read_bracket_atom(&mut self) -> Result<AtomState, ParseSmilesError> {
+		self.position += 1; // the opening bracket
+		let mass_number = self.read_number();
+
+		let symbol = match self.next_if(|c| c.is_ascii_alphabetic()) {
+			Some(first) if first.is_ascii_uppercase() => {
+				let mut symbol = first.to_string();
+				symbol.extend(self.next_if(|c| c.is_ascii_lowercase()));
+				symbol
+			},
+			Some(first) => {
+				// the two-letter aromatic symbols take precedence, e.g. `[se]`
+				let two_letters = format!("{}{}", first, self.peek().unwrap_or(' '));
+				if AROMATIC.contains(&two_letters.as_str()) {
+					self.position += 1;
+					capitalize(&two_letters)
+				} else if AROMATIC.contains(&first.to_string().as_str()) {
+					capitalize(&first.to_string())
+				} else {
+					self.position -= 1;
+					return Err(self.unexpected());
+				}
+			},
+			None => return Err(self.unexpected()),
+		};
+
+		// chirality
+		while self.next_if(|c| c == '@').is_some() {}
+
+		let hydrogens = match self.next_if(|c| c == 'H') {
+			None => 0,
+			Some(_) => self.read_number().unwrap_or(1),
+		};
+
+		let mut charge = 0;
+		if let Some(sign) = self.next_if(|c| c == '+' || c == '-') {
+			let sign = if sign == '+' { 1 } else { -1 };
+			charge = match self.read_number() {
+				Some(magnitude) => i32::try_from(magnitude).map_err(|_| self.unexpected())?,
+				// repeated signs, e.g. `++` for a charge of two
+				None => {
+					let mut magnitude = 1;
+					while self
+						.next_if(|c| c == if sign == 1 { '+' } else { '-' })
+						.is_some()
+					{
+						magnitude += 1;
+					}
+					magnitude
+				},
+			} * sign;
+		}
+
+		// the atom class
+		if self.next_if(|c| c == ':').is_some() && self.read_number().is_none() {
+			return Err(self.unexpected());
+		}
+
+		if self.next_if(|c| c == ']').is_none() {
+			return Err(self.unexpected());
+		}
+		Ok(AtomState {
+			atom: SmilesAtom {
+				symbol,
+				mass_number,
+				hydrogens,
+				charge,
+			},
+			in_brackets: true,
+			aromatic: false,
+			bond_orders: 0,
+		})
+	}
+
+	/// Reads a ring bond label, e.g. the `1`s of `C1CCCCC1` or the `%10` of `C%10CC%10`, which
+	/// either opens a ring bond or closes the one opened with the same label
+	fn read_ring_bond(&mut self) -> Result<(), ParseSmilesError> {
+		let Some(atom) = self.previous else {
+			return Err(self.unexpected());
+		};
+		let position = self.position;
+		let label = if self.next_if(|c| c == '%').is_some() {
+			// a label after `%` always has two digits
+			let digits: String = self.chars[self.position..].iter().take(2).collect();
+			if digits.len() != 2 || !digits.chars().all(|c| c.is_ascii_digit()) {
+				return Err(self.unexpected());
+			}
+			self.position += 2;
+			digits.parse().expect("Should've checked for two digits")
+		} else {
+			let digit = self
+				.next_if(|c| c.is_ascii_digit())
+				.expect("Should've peeked a digit");
+			digit.to_digit(10).expect("Should've been a digit")
+		};
+
+		let bond = self.pending_bond.take().map(|(order, _)| order);
+		match self.open_rings.remove(&label) {
+			None => {
+				self.open_rings.insert(label, (atom, bond, position));
+			},
+			Some((other_atom, other_bond, _)) => {
+				if other_atom == atom {
+					return Err(ParseSmilesError::UnexpectedChar {
+						found: self.chars[position],
+						position,
+					});
+				}
+				self.bond(other_atom, atom, bond.or(other_bond).unwrap_or(1));
+			},
+		}
+		Ok(())
+	}
+}
+
+fn capitalize(symbol: &str) -> String {
+	let mut chars = symbol.chars();
+	match chars.next() {
+		None => String::new(),
+		Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+	}
+}
+
+fn implicit_hydrogens(symbol: &str, bond_orders: u32, aromatic: bool) -> u32 {
+	let valences = ORGANIC_SUBSET
+		.iter()
+		.find(|(other, _)| *other == symbol)
+		.map_or(&[][..], |(_, valences)| *valences);
+	// an aromatic atom has one more bond's worth of electrons in the ring than its bonds show
+	let used = bond_orders + u32::from(aromatic);
+	valences
+		.iter()
+		.find(|&&valence| valence >= used)
+		.map_or(0, |valence| valence - used)
+}
+
+impl FromStr for Smiles {
+	type Err = ParseSmilesError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		SmilesReader::new(s).read()
+	}
+}
+
+/// An error in a SMILES string; each position is counted in chars from the start of the string
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseSmilesError {
+	UnexpectedChar { found: char, position: usize },
+	UnexpectedEnd { position: usize },
+	UnclosedBranch { position: usize },
+	UnclosedRing { label: u32, position: usize },
+}
+impl ParseSmilesError {
+	pub fn position(&self) -> usize {
+		match self {
+			ParseSmilesError::UnexpectedChar { position, .. }
+			| ParseSmilesError::UnexpectedEnd { position }
+			| ParseSmilesError::UnclosedBranch { position }
+			| ParseSmilesError::UnclosedRing { position, .. } => *position,
+		}
+	}
+}
+impl Display for ParseSmilesError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			ParseSmilesError::UnexpectedChar { found, .. } => {
+				write!(f, "Unexpected '{}' in SMILES", found)
+			},
+			ParseSmilesError::UnexpectedEnd { .. } => write!(f, "Unexpected end of SMILES"),
+			ParseSmilesError::UnclosedBranch { .. } => write!(f, "The branch is never closed"),
+			ParseSmilesError::UnclosedRing { label, .. } => {
+				write!(f, "Ring bond {} is never closed", label)
+			},
+		}
+	}
+}
+impl Error for ParseSmilesError {}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Totals the atoms of each element, hydrogens included
+	fn composition(smiles: &str) -> Vec<(String, u32)> {
+		let mut result: Vec<(String, u32)> = Vec::new();
+		let smiles: Smiles = smiles.parse().unwrap();
+		for atom in smiles.atoms() {
+			for (symbol, count) in [(&atom.symbol[..], 1), ("H", atom.hydrogens)] {
+				match result.iter_mut().find(|(other, _)| other == symbol) {
+					Some((_, total)) => *total += count,
+					None if count > 0 => result.push((symbol.to_string(), count)),
+					None => (),
+				}
+			}
+		}
+		result
+	}
+
+	fn counts(parts: &[(&str, u32)]) -> Vec<(String, u32)> {
+		parts
+			.iter()
+			.map(|&(symbol, count)| (symbol.to_string(), count))
+			.collect()
+	}
+
+	#[test]
+	fn adds_implicit_hydrogens() {
+		assert_eq!(composition("CCO"), counts(&[("C", 2), ("H", 6), ("O", 1)]));
+		assert_eq!(
+			composition("CC(=O)O"),
+			counts(&[("C", 2), ("H", 4), ("O", 2)])
+		);
+		assert_eq!(composition("C#N"), counts(&[("C", 1), ("H", 1), ("N", 1)]));
+		assert_eq!(composition("c1ccccc1"), counts(&[("C", 6), ("H", 6)]));
+		assert_eq!(
+			composition("C1CC%10CC1CC%10"),
+			counts(&[("C", 7), ("H", 12)])
+		);
+		assert_eq!(
+			composition("ClC(Cl)Cl"),
+			counts(&[("Cl", 3), ("C", 1), ("H", 1)])
+		);
+		assert_eq!(
+			composition("OS(=O)(=O)O"),
+			counts(&[("O", 4), ("H", 2), ("S", 1)])
+		);
+	}
+
+	#[test]
+	fn reads_bracket_atoms() {
+		let smiles: Smiles = "[13CH4].[NH4+].[O-2].[Fe++]".parse().unwrap();
+		let atoms: Vec<_> = smiles
+			.atoms()
+			.iter()
+			.map(|atom| {
+				(
+					&atom.symbol[..],
+					atom.mass_number,
+					atom.hydrogens,
+					atom.charge,
+				)
+			})
+			.collect();
+		assert_eq!(
+			atoms,
+			[
+				("C", Some(13), 4, 0),
+				("N", None, 4, 1),
+				("O", None, 0, -2),
+				("Fe", None, 0, 2)
+			]
+		);
+		// bracket atoms get no implicit hydrogens
+		assert_eq!(composition("[C]"), counts(&[("C", 1)]));
+		assert_eq!(
+			composition("[nH]1cccc1"),
+			counts(&[("N", 1), ("H", 5), ("C", 4)])
+		);
+	}
+
+	#[test]
+	fn reports_errors() {
+		let cases = [
+			(
+				"C1CC",
+				ParseSmilesError::UnclosedRing {
+					label: 1,
+					position: 1,
+				},
+			),
+			("CC(C", ParseSmilesError::UnclosedBranch { position: 2 }),
+			("CC=", ParseSmilesError::UnexpectedEnd { position: 2 }),
+			(
+				"C)C",
+				ParseSmilesError::UnexpectedChar {
+					found: ')',
+					position: 1,
+				},
+			),
+			(
+				"CXC",
+				ParseSmilesError::UnexpectedChar {
+					found: 'X',
+					position: 1,
+				},
+			),
+			("[CH4", ParseSmilesError::UnexpectedEnd { position: 4 }),
+			("", ParseSmilesError::UnexpectedEnd { position: 0 }),
+		];
+		for (smiles, error) in cases {
+			assert_eq!(smiles.parse::<Smiles>(), Err(error), "{}", smiles);
+		}
+	}
+}
@@ -1,2 +1,4 @@
 pub mod coeff_vec;
+pub mod nullspace;
 pub mod peek_iter;
+pub mod superscript;
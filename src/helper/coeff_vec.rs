@@ -1,9 +1,14 @@
-use std::ops::{Add, AddAssign, Mul, MulAssign};
+use std::{
+	cmp::Ordering,
+	ops::{Add, AddAssign, Mul, MulAssign},
+};
 
 pub type Num = i32;
 
-/// A Vec that also has coefficients associated with its elements
-#[derive(Debug, PartialEq, Default, Clone)]
+/// A Vec that also has coefficients associated with its elements. The elements keep the order in
+/// which they were added, but two CoeffVecs are equal if they have the same coefficients
+/// regardless of order.
+#[derive(Debug, Default, Clone)]
 pub struct CoeffVec<T: Clone + PartialEq> {
 	pairs: Vec<(T, Num)>,
 }
@@ -32,8 +37,58 @@ impl<T: Clone + PartialEq> CoeffVec<T> {
 			None => 0,
 		}
 	}
+
+	pub fn iter(&self) -> std::slice::Iter<'_, (T, Num)> {
+		self.pairs.iter()
+	}
+
+	pub fn len(&self) -> usize {
+		self.pairs.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.pairs.is_empty()
+	}
+
+	/// Adds the coefficients of `rhs` to these, or returns None if any of them would overflow
+	pub fn checked_add(mut self, rhs: Self) -> Option<Self> {
+		for (element, coeff) in rhs.pairs {
+			self.set_coeff(&element, self.get_coeff(&element).checked_add(coeff)?);
+		}
+		Some(self)
+	}
+
+	/// Multiplies each coefficient by `rhs`, or returns None if any of them would overflow
+	pub fn checked_mul(mut self, rhs: Num) -> Option<Self> {
+		if rhs == 0 {
+			self.pairs.clear();
+			return Some(self);
+		}
+
+		for (_, coeff) in &mut self.pairs {
+			*coeff = coeff.checked_mul(rhs)?;
+		}
+		Some(self)
+	}
+
+	/// Reorders the elements, keeping their coefficients
+	pub fn sort_by(&mut self, mut compare: impl FnMut(&T, &T) -> Ordering) {
+		self.pairs.sort_by(|(a, _), (b, _)| compare(a, b));
+	}
+}
+
+impl<T: Clone + PartialEq> PartialEq for CoeffVec<T> {
+	fn eq(&self, other: &Self) -> bool {
+		self.len() == other.len()
+			&& self
+				.pairs
+				.iter()
+				.all(|(element, coeff)| other.get_coeff(element) == *coeff)
+	}
 }
 
+impl<T: Clone + Eq> Eq for CoeffVec<T> {}
+
 impl<T: Clone + PartialEq> IntoIterator for CoeffVec<T> {
 	type Item = (T, Num);
 	type IntoIter = std::vec::IntoIter<Self::Item>;
@@ -0,0 +1,89 @@
+/// Returns a basis for the integer nullspace of the specified matrix, i.e. the set of integer
+/// vectors `v` for which `matrix * v = 0`. Each basis vector is scaled so that its entries are the
+/// smallest possible integers (their gcd is 1). `num_cols` is needed because the matrix may have no
+/// rows. Returns None if the elimination overflows, which can happen for huge entries.
+pub fn integer_nullspace(matrix: &[Vec<i64>], num_cols: usize) -> Option<Vec<Vec<i64>>> {
+	let mut rows: Vec<Vec<i128>> = matrix
+		.iter()
+		.map(|row| {
+			assert_eq!(row.len(), num_cols, "All rows should have num_cols entries");
+			row.iter().map(|&entry| i128::from(entry)).collect()
+		})
+		.collect();
+
+	// fraction-free Gauss-Jordan elimination; pivots[r] is the pivot column of row r
+	let mut pivots = Vec::new();
+	for col in 0..num_cols {
+		let next_row = pivots.len();
+		let Some(pivot_row) = (next_row..rows.len()).find(|&r| rows[r][col] != 0) else {
+			continue;
+		};
+		rows.swap(next_row, pivot_row);
+		let pivot_row = rows[next_row].clone();
+		for (r, row) in rows.iter_mut().enumerate() {
+			if r == next_row || row[col] == 0 {
+				continue;
+			}
+			let factor = row[col];
+			for (entry, &pivot_entry) in row.iter_mut().zip(&pivot_row) {
+				*entry = entry
+					.checked_mul(pivot_row[col])?
+					.checked_sub(pivot_entry.checked_mul(factor)?)?;
+			}
+			reduce_by_gcd(row)?;
+		}
+		pivots.push(col);
+	}
+
+	// every free column contributes one basis vector
+	(0..num_cols)
+		.filter(|col| !pivots.contains(col))
+		.map(|free_col| {
+			// choose the free variable's value so that every pivot variable comes out integral
+			let mut free_value = 1;
+			for (r, &pivot_col) in pivots.iter().enumerate() {
+				free_value = lcm(free_value, rows[r][pivot_col].checked_abs()?)?;
+			}
+			let mut vector = vec![0; num_cols];
+			vector[free_col] = free_value;
+			for (r, &pivot_col) in pivots.iter().enumerate() {
+				vector[pivot_col] = rows[r][free_col]
+					.checked_mul(free_value)?
+					.checked_div(rows[r][pivot_col])?
+					.checked_neg()?;
+			}
+			reduce_by_gcd(&mut vector)?;
+			vector
+				.into_iter()
+				.map(|entry| i64::try_from(entry).ok())
+				.collect()
+		})
+		.collect()
+}
+
+/// Divides the entries by their gcd, or returns None if it overflows
+fn reduce_by_gcd(vector: &mut [i128]) -> Option<()> {
+	let mut divisor = 0;
+	for &entry in vector.iter() {
+		divisor = gcd(divisor, entry)?;
+	}
+	if divisor > 1 {
+		for entry in vector {
+			*entry /= divisor;
+		}
+	}
+	Some(())
+}
+
+/// The gcd of the magnitudes, or None if one of them is too large to take the magnitude of
+fn gcd(a: i128, b: i128) -> Option<i128> {
+	let (mut a, mut b) = (a.checked_abs()?, b.checked_abs()?);
+	while b != 0 {
+		(a, b) = (b, a % b);
+	}
+	Some(a)
+}
+
+fn lcm(a: i128, b: i128) -> Option<i128> {
+	(a / gcd(a, b)?).checked_mul(b)
+}
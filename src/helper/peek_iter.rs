@@ -55,7 +55,7 @@ impl<I: Iterator> Iterator for PeekIter<I> {
 	type Item = <I as Iterator>::Item;
 
 	fn next(&mut self) -> Option<Self::Item> {
-		if self.buffer.len() > 0 {
+		if !self.buffer.is_empty() {
 			self.buffer.pop_front()
 		} else {
 			self.source.next()
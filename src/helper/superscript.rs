@@ -0,0 +1,20 @@
+/// Writes the number with superscript digits, e.g. `¹³` for 13
+pub fn to_superscript(number: u32) -> String {
+	number
+		.to_string()
+		.chars()
+		.map(|digit| match digit {
+			'0' => '⁰',
+			'1' => '¹',
+			'2' => '²',
+			'3' => '³',
+			'4' => '⁴',
+			'5' => '⁵',
+			'6' => '⁶',
+			'7' => '⁷',
+			'8' => '⁸',
+			'9' => '⁹',
+			_ => unreachable!("Should've only been decimal digits"),
+		})
+		.collect()
+}
@@ -1,5 +1,5 @@
 use crate::{
-	// chem_data::{dictionary::Dictionary, elements::PeriodicTable},
+	chem_data::{dictionary::Dictionary, elements::PeriodicTable},
 	cmd_interface::UserInputIter,
 	parse::{expression::parse_str, tokens::IntoTokenIter},
 };
@@ -10,11 +10,9 @@ pub mod helper;
 pub mod parse;
 
 pub fn do_something() {
-	// let p_table = PeriodicTable::from(std::fs::read_to_string("ptable.txt").unwrap());
-	// println!("Loaded elements:\n{}", p_table);
-	// let mut dict = Dictionary::new();
-	// dict.load_elements(&p_table);
-	// println!("Dictionary:\n{:#?}", dict);
+	let p_table = PeriodicTable::standard();
+	let mut dict = Dictionary::new();
+	dict.load_elements(&p_table);
 
 	let file_contents = std::fs::read_to_string("input.txt").unwrap();
 	let _file_lines = file_contents.lines();
@@ -25,16 +23,20 @@ pub fn do_something() {
 		println!("You entered: {}", line);
 		println!("\nTokenized as: =============================");
 		for token in line.chars().into_token_iter() {
-			print!("{:?}, ", token);
+			print!("{:?}, ", token.token);
 		}
-		println!("");
-		match parse_str(&line) {
+		println!();
+		match parse_str(line) {
 			Ok(expr) => {
 				println!("\nParsed as =============================");
 				println!("{:?}", expr);
+				match expr.evaluate(&dict) {
+					Ok(value) => println!("\nEvaluates to: {}", value),
+					Err(error) => println!("Encountered error: {:?}", error),
+				}
 			},
 			Err(error) => {
-				println!("Encountered error: {:?}", error);
+				println!("Encountered error: {}", error.render(line));
 			},
 		}
 	}
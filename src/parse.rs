@@ -1,4 +1,4 @@
-// mod equations;
 pub mod expression;
 pub mod parse_with_dict;
+pub mod span;
 pub mod tokens;
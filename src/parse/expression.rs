@@ -1,32 +1,233 @@
-use std::fmt::Debug;
-
-use crate::chem_data::dictionary::{Dictionary, Value, DictAccessError, UndefinedIdentifierError, BadTypeError};
-use crate::chem_data::elements::Element;
-use crate::chem_data::formulas::MolecularFormula;
+use crate::chem_data::chem_eqn::{AddSpeciesError, ChemEqn, Num};
+use crate::chem_data::dictionary::{
+	BadTypeError, DataType, Dictionary, UndefinedIdentifierError, Value,
+};
+use crate::chem_data::elements::{Element, ELECTRON};
+use crate::chem_data::formulas::{FormulaArithmeticError, FormulaPart, MolecularFormula};
+use crate::chem_data::methods::MethodError;
+use crate::chem_data::quantity::{IncompatibleUnitsError, Quantity, QuantityArithmeticError};
+use crate::chem_data::real_number::{ParseRealNumberError, RealNumber};
+use crate::chem_data::smiles::Smiles;
 
 mod parser;
+mod printer;
 
+pub use parser::parse_script;
 pub use parser::parse_str;
+pub use parser::parse_str_recovering;
 pub use parser::parse_tokens;
+pub use parser::parse_tokens_recovering;
+pub use parser::register_special_syntax;
+pub use parser::ExpressionBuilder;
 pub use parser::ParseError;
+pub use parser::SpecialSyntax;
 
-pub trait Expression {
-	fn evaluate<'a>(self: Box<Self>, dict: &'a Dictionary) -> Result<Value<'a>, EvaluationError<'a>>;
-
-	fn forehead(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result;
+/// A parsed expression. Each kind of expression has its own node with the parts it was parsed
+/// from, so an expression can be inspected or rewritten before (or instead of) being evaluated;
+/// see `children` and `walk` for going through the expressions nested inside it. Parentheses that
+/// only group are not kept, since the nesting of the nodes already says what they grouped.
+///
+/// Displaying an expression writes it back out as source in a canonical form, e.g. `(1+2) * 3`
+/// is displayed as `(1 + 2) * 3`; parsing that source gives back an equal expression.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+	Identifier(Identifier),
+	Isotope(IsotopeExpr),
+	String(StringLiteral),
+	Integer(IntegerLiteral),
+	Real(RealLiteral),
+	Tuple(TupleExpr),
+	Call(CallExpr),
+	Infix(InfixOperationExpr),
+	MolecularFormula(MolecularFormulaExpr),
+	CondensedFormula(CondensedFormulaExpr),
+	Coefficient(CoefficientExpr),
+	ChemEqn(ChemEqnExpr),
+	Smiles(SmilesExpr),
+	Quantity(QuantityExpr),
+	Error(ErrorExpr),
 }
-impl Debug for dyn Expression {
-	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		self.forehead(f)
+
+impl Expression {
+	/// Evaluates the expression with the values in the dictionary. The expression is left as it
+	/// was, so it can be parsed once and then evaluated any number of times, e.g. with different
+	/// dictionaries.
+	pub fn evaluate<'a>(&self, dict: &Dictionary<'a>) -> Result<Value<'a>, EvaluationError<'a>> {
+		match self {
+			Expression::Identifier(expr) => expr.evaluate(dict),
+			Expression::Isotope(expr) => expr.evaluate(dict),
+			Expression::String(expr) => expr.evaluate(dict),
+			Expression::Integer(expr) => expr.evaluate(dict),
+			Expression::Real(expr) => expr.evaluate(dict),
+			Expression::Tuple(expr) => expr.evaluate(dict),
+			Expression::Call(expr) => expr.evaluate(dict),
+			Expression::Infix(expr) => expr.evaluate(dict),
+			Expression::MolecularFormula(expr) => expr.evaluate(dict),
+			Expression::CondensedFormula(expr) => expr.evaluate(dict),
+			Expression::Coefficient(expr) => expr.evaluate(dict),
+			Expression::ChemEqn(expr) => expr.evaluate(dict),
+			Expression::Smiles(expr) => expr.evaluate(dict),
+			Expression::Quantity(expr) => expr.evaluate(dict),
+			Expression::Error(expr) => expr.evaluate(dict),
+		}
+	}
+
+	/// If the expression can name a method, i.e. can appear after the `.` operator, returns the
+	/// name of the method and the expressions for its arguments.
+	fn as_method_call(&self) -> Option<(&str, &[Expression])> {
+		match self {
+			Expression::Identifier(identifier) => Some((&identifier.name, &[])),
+			Expression::Call(call) => Some((&call.name, &call.args)),
+			_ => None,
+		}
+	}
+
+	/// The expressions directly inside this one, in the order they were written. The symbols of a
+	/// formula are identifiers (or isotopes), so they count as children too. The method named after
+	/// a `.` isn't evaluated as an expression, so only its arguments count, e.g. just `O` for the
+	/// rhs of `$H2O.count(O)`.
+	pub fn children(&self) -> Vec<&Expression> {
+		let mut children = Vec::new();
+		match self {
+			Expression::Identifier(_)
+			| Expression::String(_)
+			| Expression::Integer(_)
+			| Expression::Real(_)
+			| Expression::Smiles(_)
+			| Expression::Quantity(_)
+			| Expression::Error(_) => (),
+			Expression::Isotope(expr) => children.push(&*expr.element),
+			Expression::Tuple(expr) => children.extend(&expr.values),
+			Expression::Call(expr) => children.extend(&expr.args),
+			Expression::Infix(expr) => {
+				children.push(&*expr.lhs);
+				match (expr.operator, &*expr.rhs) {
+					(InfixOperator::FunctionCall, Expression::Identifier(_)) => (),
+					(InfixOperator::FunctionCall, Expression::Call(call)) => {
+						children.extend(&call.args)
+					},
+					(_, rhs) => children.push(rhs),
+				}
+			},
+			Expression::MolecularFormula(expr) => {
+				children.extend(&expr.symbols_and_subscripts);
+				children.extend(
+					expr.charge
+						.iter()
+						.flat_map(|charge| charge.magnitude.as_deref()),
+				);
+			},
+			Expression::CondensedFormula(expr) => {
+				for part in &expr.parts {
+					part.push_elements(&mut children);
+				}
+				children.extend(
+					expr.charge
+						.iter()
+						.flat_map(|charge| charge.magnitude.as_deref()),
+				);
+			},
+			Expression::Coefficient(expr) => children.push(&*expr.species),
+			Expression::ChemEqn(expr) => {
+				let species = expr.reactants.iter().chain(&expr.products);
+				children.extend(species.map(|(_, species)| species));
+			},
+		}
+		children
+	}
+
+	/// Like `children`, but allows the children to be rewritten in place
+	pub fn children_mut(&mut self) -> Vec<&mut Expression> {
+		let mut children = Vec::new();
+		match self {
+			Expression::Identifier(_)
+			| Expression::String(_)
+			| Expression::Integer(_)
+			| Expression::Real(_)
+			| Expression::Smiles(_)
+			| Expression::Quantity(_)
+			| Expression::Error(_) => (),
+			Expression::Isotope(expr) => children.push(&mut *expr.element),
+			Expression::Tuple(expr) => children.extend(&mut expr.values),
+			Expression::Call(expr) => children.extend(&mut expr.args),
+			Expression::Infix(expr) => {
+				children.push(&mut *expr.lhs);
+				match (expr.operator, &mut *expr.rhs) {
+					(InfixOperator::FunctionCall, Expression::Identifier(_)) => (),
+					(InfixOperator::FunctionCall, Expression::Call(call)) => {
+						children.extend(&mut call.args)
+					},
+					(_, rhs) => children.push(rhs),
+				}
+			},
+			Expression::MolecularFormula(expr) => {
+				children.extend(&mut expr.symbols_and_subscripts);
+				let charge = expr.charge.iter_mut();
+				children.extend(charge.flat_map(|charge| charge.magnitude.as_deref_mut()));
+			},
+			Expression::CondensedFormula(expr) => {
+				for part in &mut expr.parts {
+					part.push_elements_mut(&mut children);
+				}
+				let charge = expr.charge.iter_mut();
+				children.extend(charge.flat_map(|charge| charge.magnitude.as_deref_mut()));
+			},
+			Expression::Coefficient(expr) => children.push(&mut *expr.species),
+			Expression::ChemEqn(expr) => {
+				let species = expr.reactants.iter_mut().chain(&mut expr.products);
+				children.extend(species.map(|(_, species)| species));
+			},
+		}
+		children
+	}
+
+	/// Calls `visit` on this expression and then on every expression nested inside it, parents
+	/// before their children, e.g. to collect the identifiers an expression refers to
+	pub fn walk<'e>(&'e self, visit: &mut impl FnMut(&'e Expression)) {
+		visit(self);
+		for child in self.children() {
+			child.walk(visit);
+		}
+	}
+
+	/// Like `walk`, but allows each expression to be rewritten in place. The children of an
+	/// expression are visited after `visit` has rewritten it, so a replacement is walked too.
+	pub fn walk_mut(&mut self, visit: &mut impl FnMut(&mut Expression)) {
+		visit(self);
+		for child in self.children_mut() {
+			child.walk_mut(visit);
+		}
 	}
 }
 
+#[derive(Debug)]
 pub enum EvaluationError<'a> {
-	UndefinedIdentifier(UndefinedIdentifierError<'a>),
+	UndefinedIdentifier(UndefinedIdentifierError),
 	BadType(BadTypeError<'a>),
+	MalformedReal(ParseRealNumberError),
+	Overflow,
+	DivisionByZero,
+	/// A power with no real result, i.e. a negative base with a fractional power or a zero base
+	/// with a negative power
+	UndefinedPower,
+	ExpectedMethodName,
+	/// A method was called like a function but without any arguments to serve as its receiver
+	ExpectedReceiver {
+		method: String,
+	},
+	UnknownIsotope {
+		symbol: String,
+		mass_number: u32,
+	},
+	Method(MethodError<'a>),
+	Formula(FormulaArithmeticError),
+	Units(IncompatibleUnitsError),
+	Species(AddSpeciesError),
+	/// The expression was a placeholder for source that failed to parse
+	Parse(ParseError),
 }
-impl<'a> From<UndefinedIdentifierError<'a>> for EvaluationError<'a> {
-	fn from(value: UndefinedIdentifierError<'a>) -> Self {
+impl From<UndefinedIdentifierError> for EvaluationError<'_> {
+	fn from(value: UndefinedIdentifierError) -> Self {
 		EvaluationError::UndefinedIdentifier(value)
 	}
 }
@@ -35,106 +236,172 @@ impl<'a> From<BadTypeError<'a>> for EvaluationError<'a> {
 		EvaluationError::BadType(value)
 	}
 }
-
-#[derive(Debug)]
-pub struct Identifier {
-	name: String,
+impl<'a> From<MethodError<'a>> for EvaluationError<'a> {
+	fn from(value: MethodError<'a>) -> Self {
+		EvaluationError::Method(value)
+	}
 }
-
-impl Expression for Identifier {
-	fn evaluate<'a>(self: Box<Self>, dict: &'a Dictionary) -> Result<Value<'a>, EvaluationError<'a>> {
-		Ok(dict.get_value(&self.name).cloned()?)
+impl From<FormulaArithmeticError> for EvaluationError<'_> {
+	fn from(value: FormulaArithmeticError) -> Self {
+		EvaluationError::Formula(value)
 	}
-
-	fn forehead(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		self.fmt(f)
+}
+impl From<IncompatibleUnitsError> for EvaluationError<'_> {
+	fn from(value: IncompatibleUnitsError) -> Self {
+		EvaluationError::Units(value)
 	}
 }
-
-#[derive(Debug)]
-struct StringLiteral {
-	content: String,
+impl From<QuantityArithmeticError> for EvaluationError<'_> {
+	fn from(value: QuantityArithmeticError) -> Self {
+		match value {
+			QuantityArithmeticError::IncompatibleUnits(err) => EvaluationError::Units(err),
+			QuantityArithmeticError::Overflow => EvaluationError::Overflow,
+		}
+	}
 }
-
-impl Expression for StringLiteral {
-	fn evaluate<'a>(self: Box<Self>, _dict: &'a Dictionary) -> Result<Value<'a>, EvaluationError<'a>> {
-		Ok(Value::String(self.content))
+impl From<AddSpeciesError> for EvaluationError<'_> {
+	fn from(value: AddSpeciesError) -> Self {
+		EvaluationError::Species(value)
 	}
-
-	fn forehead(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		self.fmt(f)
+}
+impl From<ParseRealNumberError> for EvaluationError<'_> {
+	fn from(value: ParseRealNumberError) -> Self {
+		EvaluationError::MalformedReal(value)
 	}
 }
 
-#[derive(Debug)]
-struct IntegerLiteral {
-	value: u32,
+#[derive(Debug, Clone, PartialEq)]
+pub struct Identifier {
+	pub name: String,
 }
 
-impl Expression for IntegerLiteral {
-	fn evaluate<'a>(self: Box<Self>, _dict: &'a Dictionary) -> Result<Value<'a>, EvaluationError<'a>> {
-		Ok(Value::Integer(self.value))
+impl Identifier {
+	fn evaluate<'a>(&self, dict: &Dictionary<'a>) -> Result<Value<'a>, EvaluationError<'a>> {
+		Ok(dict.get_value(&self.name)?.into_owned())
 	}
+}
 
-	fn forehead(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		self.fmt(f)
+/// An isotope of an element, e.g. `[13]C`
+#[derive(Debug, Clone, PartialEq)]
+pub struct IsotopeExpr {
+	pub mass_number: u32,
+	pub element: Box<Expression>,
+}
+
+impl IsotopeExpr {
+	fn evaluate<'a>(&self, dict: &Dictionary<'a>) -> Result<Value<'a>, EvaluationError<'a>> {
+		let element = self.element.evaluate(dict)?.as_type::<&Element>()?;
+		let isotope = dict.get_isotope(element, self.mass_number).ok_or_else(|| {
+			EvaluationError::UnknownIsotope {
+				symbol: element.symbol.clone(),
+				mass_number: self.mass_number,
+			}
+		})?;
+		Ok(Value::ElementRef(isotope))
 	}
 }
 
-#[derive(Debug)]
-struct RealLiteral {
-	value: String,
+#[derive(Debug, Clone, PartialEq)]
+pub struct StringLiteral {
+	pub content: String,
 }
 
-impl Expression for RealLiteral {
-	fn evaluate<'a>(self: Box<Self>, dict: &'a Dictionary) -> Result<Value<'a>, EvaluationError<'a>> {
-		todo!();
+impl StringLiteral {
+	fn evaluate<'a>(&self, _dict: &Dictionary<'a>) -> Result<Value<'a>, EvaluationError<'a>> {
+		Ok(Value::String(self.content.clone()))
 	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IntegerLiteral {
+	pub value: u32,
+}
 
-	fn forehead(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		self.fmt(f)
+impl IntegerLiteral {
+	fn evaluate<'a>(&self, _dict: &Dictionary<'a>) -> Result<Value<'a>, EvaluationError<'a>> {
+		Ok(Value::Integer(self.value))
 	}
 }
 
-#[derive(Debug)]
-struct TupleExpr {
-	values: Vec<Box<dyn Expression>>,
+/// A real number as it was written, e.g. `1.50`, which is only parsed when evaluated
+#[derive(Debug, Clone, PartialEq)]
+pub struct RealLiteral {
+	pub value: String,
 }
 
-impl Expression for TupleExpr {
-	fn evaluate<'a>(self: Box<Self>, dict: &'a Dictionary) -> Result<Value<'a>, EvaluationError<'a>> {
-		todo!();
+impl RealLiteral {
+	fn evaluate<'a>(&self, _dict: &Dictionary<'a>) -> Result<Value<'a>, EvaluationError<'a>> {
+		Ok(Value::RealNumber(self.value.parse::<RealNumber>()?))
 	}
+}
 
-	fn forehead(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		self.fmt(f)
+/// A tuple, e.g. `(1, 2)` or `(1,)`. A single parenthesized expression without a trailing comma,
+/// e.g. `(a + b)`, is just the expression itself rather than a 1-tuple.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TupleExpr {
+	pub values: Vec<Expression>,
+}
+
+impl TupleExpr {
+	fn evaluate<'a>(&self, dict: &Dictionary<'a>) -> Result<Value<'a>, EvaluationError<'a>> {
+		let mut values = Vec::new();
+		for expr in &self.values {
+			values.push(expr.evaluate(dict)?);
+		}
+		Ok(Value::Tuple(values))
 	}
 }
 
-#[derive(Debug)]
-struct SpecialSyntaxExpr {
-	syntax_name: Identifier,
-	inner_expr: Box<dyn Expression>,
+/// Stands in for source that failed to parse, so that the rest of the expression can still be
+/// built and more errors found; see `parse_tokens_recovering`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorExpr {
+	pub error: ParseError,
 }
 
-impl Expression for SpecialSyntaxExpr {
-	fn evaluate<'a>(self: Box<Self>, dict: &'a Dictionary) -> Result<Value<'a>, EvaluationError<'a>> {
-		todo!();
+impl ErrorExpr {
+	fn evaluate<'a>(&self, _dict: &Dictionary<'a>) -> Result<Value<'a>, EvaluationError<'a>> {
+		Err(EvaluationError::Parse(self.error.clone()))
 	}
+}
 
-	fn forehead(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		self.fmt(f)
+/// A call with parenthesized arguments, e.g. `react_with($Al)`. After a `.` the receiver is the
+/// value before the `.`; on its own, the first argument is the receiver, so `count($H2O, H)` is the
+/// same as `$H2O.count(H)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallExpr {
+	pub name: String,
+	pub args: Vec<Expression>,
+}
+
+impl CallExpr {
+	fn evaluate<'a>(&self, dict: &Dictionary<'a>) -> Result<Value<'a>, EvaluationError<'a>> {
+		let mut args = Vec::new();
+		for arg_expr in &self.args {
+			args.push(arg_expr.evaluate(dict)?);
+		}
+		if args.is_empty() {
+			return Err(EvaluationError::ExpectedReceiver {
+				method: self.name.clone(),
+			});
+		}
+		let receiver = args.remove(0);
+		Ok(dict.methods().call(receiver, &self.name, args)?)
 	}
 }
 
-#[derive(Debug)]
-struct InfixOperationsExpr {
-	operands: Vec<Box<dyn Expression>>,
-	operators: Vec<InfixOperator>,
+/// A single infix operation. Precedence is applied when parsing, so `1 + 2 * 3` is a `+` whose
+/// right-hand side is the `*`, and `a.count(b)` is a `.` whose right-hand side is the call that
+/// names the method.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InfixOperationExpr {
+	pub lhs: Box<Expression>,
+	pub operator: InfixOperator,
+	pub rhs: Box<Expression>,
 }
 
-#[derive(Debug, Clone, Copy)]
-enum InfixOperator {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InfixOperator {
 	FunctionCall,
 	Plus,
 	Minus,
@@ -143,29 +410,173 @@ enum InfixOperator {
 	Pow,
 }
 
-impl Expression for InfixOperationsExpr {
-	fn evaluate<'a>(self: Box<Self>, dict: &'a Dictionary) -> Result<Value<'a>, EvaluationError<'a>> {
-		todo!();
+impl InfixOperator {
+	/// Operators with higher precedence bind more tightly
+	pub fn precedence(self) -> u8 {
+		match self {
+			InfixOperator::Plus | InfixOperator::Minus => 1,
+			InfixOperator::Mul | InfixOperator::Div => 2,
+			InfixOperator::Pow => 3,
+			InfixOperator::FunctionCall => 4,
+		}
+	}
+
+	pub fn is_right_associative(self) -> bool {
+		matches!(self, InfixOperator::Pow)
 	}
 
-	fn forehead(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		self.fmt(f)
+	pub fn symbol(self) -> &'static str {
+		match self {
+			InfixOperator::FunctionCall => ".",
+			InfixOperator::Plus => "+",
+			InfixOperator::Minus => "-",
+			InfixOperator::Mul => "*",
+			InfixOperator::Div => "/",
+			InfixOperator::Pow => "^",
+		}
+	}
+
+	fn apply<'a>(self, lhs: Value<'a>, rhs: Value<'a>) -> Result<Value<'a>, EvaluationError<'a>> {
+		use InfixOperator::*;
+
+		let checked =
+			|result: Option<u32>| result.map(Value::Integer).ok_or(EvaluationError::Overflow);
+		match (self, lhs, rhs) {
+			(Plus, Value::Integer(a), Value::Integer(b)) => checked(a.checked_add(b)),
+			// a negative difference or an inexact quotient falls through to exact real arithmetic
+			(Minus, Value::Integer(a), Value::Integer(b)) if a >= b => Ok(Value::Integer(a - b)),
+			(Mul, Value::Integer(a), Value::Integer(b)) => checked(a.checked_mul(b)),
+			(Div, Value::Integer(a), Value::Integer(b)) if b != 0 && a % b == 0 => {
+				Ok(Value::Integer(a / b))
+			},
+			(Pow, Value::Integer(a), Value::Integer(b)) => checked(a.checked_pow(b)),
+			(Pow, Value::RealNumber(a), Value::Integer(b)) => {
+				let power = i32::try_from(b).map_err(|_| EvaluationError::Overflow)?;
+				Ok(Value::RealNumber(
+					a.checked_powi(power).ok_or(EvaluationError::Overflow)?,
+				))
+			},
+			(
+				operator,
+				lhs @ (Value::Integer(_) | Value::RealNumber(_)),
+				rhs @ (Value::Integer(_) | Value::RealNumber(_)),
+			) => {
+				let (a, b) = (into_real(lhs), into_real(rhs));
+				let result = match operator {
+					Plus => a.checked_add(b),
+					Minus => a.checked_sub(b),
+					Mul => a.checked_mul(b),
+					Div if b.value() == 0.0 => return Err(EvaluationError::DivisionByZero),
+					Div => a.checked_div(b),
+					Pow if (a.value() < 0.0 && b.value().fract() != 0.0)
+						|| (a.value() == 0.0 && b.value() < 0.0) =>
+					{
+						return Err(EvaluationError::UndefinedPower)
+					},
+					Pow => a.checked_pow(b),
+					FunctionCall => unreachable!("Method calls should've been applied already"),
+				};
+				Ok(Value::RealNumber(result.ok_or(EvaluationError::Overflow)?))
+			},
+			(Pow, Value::Quantity(a), Value::Integer(b)) => {
+				let power = i32::try_from(b).map_err(|_| EvaluationError::Overflow)?;
+				Ok(Value::Quantity(a.checked_powi(power)?))
+			},
+			(
+				operator @ (Plus | Minus | Mul | Div),
+				lhs @ (Value::Integer(_) | Value::RealNumber(_) | Value::Quantity(_)),
+				rhs @ (Value::Integer(_) | Value::RealNumber(_) | Value::Quantity(_)),
+			) => {
+				let (a, b) = (into_quantity(lhs), into_quantity(rhs));
+				let result = match operator {
+					Plus => a.checked_add(b)?,
+					Minus => a.checked_sub(b)?,
+					Mul => a.checked_mul(b)?,
+					Div if b.value().value() == 0.0 => return Err(EvaluationError::DivisionByZero),
+					Div => a.checked_div(b)?,
+					_ => unreachable!("Should've matched only the arithmetic operators"),
+				};
+				// units that cancel out leave just a number, e.g. `g/mol * mol/g`
+				if result.units().is_dimensionless() {
+					Ok(Value::RealNumber(result.value().clone()))
+				} else {
+					Ok(Value::Quantity(result))
+				}
+			},
+			(Plus, Value::MolecularFormula(a), Value::MolecularFormula(b)) => {
+				Ok(Value::MolecularFormula(a.checked_add(b)?))
+			},
+			(Minus, Value::MolecularFormula(a), Value::MolecularFormula(b)) => {
+				Ok(Value::MolecularFormula(a.checked_sub(b)?))
+			},
+			(Mul, Value::MolecularFormula(formula), Value::Integer(factor))
+			| (Mul, Value::Integer(factor), Value::MolecularFormula(formula)) => {
+				Ok(Value::MolecularFormula(formula.checked_mul(factor)?))
+			},
+			(operator, lhs, rhs) => Err(bad_operands(operator, &lhs, &rhs)),
+		}
 	}
 }
 
-#[derive(Debug)]
-struct MolecularFormulaExpr {
-	symbols_and_subscripts: Vec<Box<dyn Expression>>,
-	_charge: Option<(bool, Box<dyn Expression>)>,
-	// true is positive, false is negative
+/// Converts a numeric value to a RealNumber; integers are exact, so they never limit the
+/// significant figures of a result.
+fn into_real(value: Value) -> RealNumber {
+	match value {
+		Value::Integer(integer) => RealNumber::exact(f64::from(integer)),
+		Value::RealNumber(real) => real,
+		_ => unreachable!("Should've checked for a numeric type"),
+	}
+}
+
+fn into_quantity(value: Value) -> Quantity {
+	match value {
+		Value::Quantity(quantity) => quantity,
+		number => Quantity::from(into_real(number)),
+	}
+}
+
+fn bad_operands<'a>(operator: InfixOperator, lhs: &Value, rhs: &Value) -> EvaluationError<'a> {
+	EvaluationError::BadType(BadTypeError::Operands {
+		operator: operator.symbol(),
+		lhs_type: DataType::from(lhs),
+		rhs_type: DataType::from(rhs),
+	})
+}
+
+impl InfixOperationExpr {
+	fn evaluate<'a>(&self, dict: &Dictionary<'a>) -> Result<Value<'a>, EvaluationError<'a>> {
+		let lhs = self.lhs.evaluate(dict)?;
+		if self.operator != InfixOperator::FunctionCall {
+			return self.operator.apply(lhs, self.rhs.evaluate(dict)?);
+		}
+
+		// the right-hand side of a `.` names a method rather than being evaluated
+		let (name, arg_exprs) = self
+			.rhs
+			.as_method_call()
+			.ok_or(EvaluationError::ExpectedMethodName)?;
+		let mut args = Vec::new();
+		for arg_expr in arg_exprs {
+			args.push(arg_expr.evaluate(dict)?);
+		}
+		Ok(dict.methods().call(lhs, name, args)?)
+	}
+}
+
+/// A formula of element symbols and subscripts, e.g. `$H2O` or `$NH4+`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MolecularFormulaExpr {
+	/// Each symbol (an identifier or isotope) followed by its subscript if it has one
+	pub symbols_and_subscripts: Vec<Expression>,
+	pub charge: Option<ChargeExpr>,
 }
 
-impl Expression for MolecularFormulaExpr {
-	fn evaluate<'a>(self: Box<Self>, dict: &'a Dictionary) -> Result<Value<'a>, EvaluationError<'a>> {
+impl MolecularFormulaExpr {
+	fn evaluate<'a>(&self, dict: &Dictionary<'a>) -> Result<Value<'a>, EvaluationError<'a>> {
 		let mut result = MolecularFormula::new();
 
 		let mut values = Vec::new();
-		for expr in self.symbols_and_subscripts.into_iter() {
+		for expr in &self.symbols_and_subscripts {
 			values.push(expr.evaluate(dict)?);
 		}
 		let mut values = values.into_iter().peekable();
@@ -175,30 +586,845 @@ impl Expression for MolecularFormulaExpr {
 				None => 1,
 				Some(value) => value.as_type::<u32>().expect("Should've checked int type"),
 			};
-			result.set_subscr(element, result.get_subscr(element) + subscript);
+			let new_subscript = result
+				.get_subscr(element)
+				.checked_add(subscript)
+				.ok_or(FormulaArithmeticError::Overflow)?;
+			result.try_set_subscr(element, new_subscript)?;
 		}
+
+		apply_charge(&mut result, self.charge.as_ref(), dict)?;
 		Ok(Value::MolecularFormula(result))
+	}
+}
 
-		// TODO incorporate charges
+/// The charge written at the end of a formula, e.g. the `-2` of `$SO4-2`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChargeExpr {
+	pub is_positive: bool,
+	/// A missing magnitude means a magnitude of one, e.g. `$NH4+`
+	pub magnitude: Option<Box<Expression>>,
+}
+
+/// Sets the charge of the formula from its (possibly absent) charge expression. Without an
+/// explicit charge, each electron contributes a negative charge, e.g. `$e`
+fn apply_charge<'a>(
+	formula: &mut MolecularFormula<'a>,
+	charge: Option<&ChargeExpr>,
+	dict: &Dictionary<'a>,
+) -> Result<(), EvaluationError<'a>> {
+	let charge = match charge {
+		None => -(formula.get_subscr(&ELECTRON) as i32),
+		Some(ChargeExpr {
+			is_positive,
+			magnitude,
+		}) => {
+			let magn = match magnitude {
+				None => 1,
+				Some(magn) => {
+					let magn = magn.evaluate(dict)?.as_type::<u32>()?;
+					i32::try_from(magn).map_err(|_| EvaluationError::Overflow)?
+				},
+			};
+			if *is_positive {
+				magn
+			} else {
+				-magn
+			}
+		},
+	};
+	formula.set_charge(charge);
+	Ok(())
+}
+
+/// A piece of a condensed formula; see FormulaPart
+#[derive(Debug, Clone, PartialEq)]
+pub enum CondensedPartExpr {
+	/// An identifier or isotope and its subscript
+	Element(Expression, u32),
+	Group(Vec<CondensedPartExpr>, u32),
+	Adduct(u32, Vec<CondensedPartExpr>),
+}
+
+impl CondensedPartExpr {
+	fn evaluate<'a>(&self, dict: &Dictionary<'a>) -> Result<FormulaPart<'a>, EvaluationError<'a>> {
+		let evaluate_all = |parts: &[CondensedPartExpr]| -> Result<_, EvaluationError<'a>> {
+			let mut result = Vec::new();
+			for part in parts {
+				result.push(part.evaluate(dict)?);
+			}
+			Ok(result)
+		};
+		Ok(match self {
+			CondensedPartExpr::Element(element, subscript) => {
+				FormulaPart::Element(element.evaluate(dict)?.as_type::<&Element>()?, *subscript)
+			},
+			CondensedPartExpr::Group(parts, subscript) => {
+				FormulaPart::Group(evaluate_all(parts)?, *subscript)
+			},
+			CondensedPartExpr::Adduct(coefficient, parts) => {
+				FormulaPart::Adduct(*coefficient, evaluate_all(parts)?)
+			},
+		})
+	}
+
+	/// Adds the element expressions in this part, however deeply nested, to `elements`
+	fn push_elements<'e>(&'e self, elements: &mut Vec<&'e Expression>) {
+		match self {
+			CondensedPartExpr::Element(element, _) => elements.push(element),
+			CondensedPartExpr::Group(parts, _) | CondensedPartExpr::Adduct(_, parts) => {
+				for part in parts {
+					part.push_elements(elements);
+				}
+			},
+		}
 	}
 
-	fn forehead(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		self.fmt(f)
+	fn push_elements_mut<'e>(&'e mut self, elements: &mut Vec<&'e mut Expression>) {
+		match self {
+			CondensedPartExpr::Element(element, _) => elements.push(element),
+			CondensedPartExpr::Group(parts, _) | CondensedPartExpr::Adduct(_, parts) => {
+				for part in parts {
+					part.push_elements_mut(elements);
+				}
+			},
+		}
 	}
 }
 
-#[derive(Debug)]
-struct CondensedFormulaExpr {
-	subformulas_and_subscripts: Vec<Box<dyn Expression>>,
-	charge: Option<(bool, Box<dyn Expression>)>,
+/// A formula with parenthesized groups and/or adducts, e.g. `$$Fe2(SO4)3` or `$$CuSO4.5H2O`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CondensedFormulaExpr {
+	/// The parts of the formula itself, followed by an Adduct part for each adduct
+	pub parts: Vec<CondensedPartExpr>,
+	pub charge: Option<ChargeExpr>,
 }
 
-impl Expression for CondensedFormulaExpr {
-	fn evaluate<'a>(self: Box<Self>, dict: &'a Dictionary) -> Result<Value<'a>, EvaluationError<'a>> {
-		todo!();
+impl CondensedFormulaExpr {
+	fn evaluate<'a>(&self, dict: &Dictionary<'a>) -> Result<Value<'a>, EvaluationError<'a>> {
+		let mut parts = Vec::new();
+		for part in &self.parts {
+			parts.push(part.evaluate(dict)?);
+		}
+		let mut result = MolecularFormula::from_parts(parts)?;
+		apply_charge(&mut result, self.charge.as_ref(), dict)?;
+		Ok(Value::MolecularFormula(result))
 	}
+}
+
+/// A species with a leading integer coefficient, e.g. `2$H2O`. Inside a chemical equation the
+/// coefficient counts molecules; on its own, the species' atoms are multiplied by the coefficient.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoefficientExpr {
+	pub coefficient: u32,
+	pub species: Box<Expression>,
+}
+
+impl CoefficientExpr {
+	fn evaluate<'a>(&self, dict: &Dictionary<'a>) -> Result<Value<'a>, EvaluationError<'a>> {
+		let species = self.species.evaluate(dict)?.as_type::<MolecularFormula>()?;
+		Ok(Value::MolecularFormula(
+			species.checked_mul(self.coefficient)?,
+		))
+	}
+}
+
+/// The species on one side of a chemical equation, each with its coefficient
+pub type EquationSide = Vec<(u32, Expression)>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChemEqnExpr {
+	pub reactants: EquationSide,
+	pub products: EquationSide,
+	pub reversible: bool,
+}
+
+impl ChemEqnExpr {
+	fn evaluate<'a>(&self, dict: &Dictionary<'a>) -> Result<Value<'a>, EvaluationError<'a>> {
+		let mut result = ChemEqn::new();
+		result.set_reversible(self.reversible);
+		for (sign, side) in [(-1, &self.reactants), (1, &self.products)] {
+			for (coefficient, expr) in side {
+				let species = expr.evaluate(dict)?.as_type::<MolecularFormula>()?;
+				let coeff = Num::try_from(*coefficient).map_err(|_| EvaluationError::Overflow)?;
+				result.add_species(species, sign * coeff)?;
+			}
+		}
+		Ok(Value::ChemEqn(result))
+	}
+}
+
+/// A molecule written in SMILES notation, e.g. `smiles!{CC(=O)O}`, which evaluates to its molecular
+/// formula
+#[derive(Debug, Clone, PartialEq)]
+pub struct SmilesExpr {
+	pub smiles: Smiles,
+	/// The SMILES string as it was written, which is kept for displaying since the structure
+	/// isn't
+	pub text: String,
+}
+
+impl SmilesExpr {
+	fn evaluate<'a>(&self, dict: &Dictionary<'a>) -> Result<Value<'a>, EvaluationError<'a>> {
+		let mut result = MolecularFormula::new();
+		let mut add_atoms = |element: &'a Element, count: u32| -> Result<(), EvaluationError<'a>> {
+			let subscript = result
+				.get_subscr(element)
+				.checked_add(count)
+				.ok_or(FormulaArithmeticError::Overflow)?;
+			Ok(result.try_set_subscr(element, subscript)?)
+		};
+
+		let mut charge: i32 = 0;
+		for atom in self.smiles.atoms() {
+			let mut element = dict
+				.get_value(&atom.symbol)?
+				.into_owned()
+				.as_type::<&Element>()?;
+			if let Some(mass_number) = atom.mass_number {
+				element = dict.get_isotope(element, mass_number).ok_or_else(|| {
+					EvaluationError::UnknownIsotope {
+						symbol: element.symbol.clone(),
+						mass_number,
+					}
+				})?;
+			}
+			add_atoms(element, 1)?;
+			if atom.hydrogens > 0 {
+				let hydrogen = dict.get_value("H")?.into_owned().as_type::<&Element>()?;
+				add_atoms(hydrogen, atom.hydrogens)?;
+			}
+			charge = charge
+				.checked_add(atom.charge)
+				.ok_or(EvaluationError::Overflow)?;
+		}
+		result.set_charge(charge);
+		Ok(Value::MolecularFormula(result))
+	}
+}
+
+/// A measurement with units, e.g. `units!{12.5 g/mol}`
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuantityExpr {
+	pub quantity: Quantity,
+	/// The quantity as it was written, e.g. `12.5 g/mol`
+	pub text: String,
+}
+
+impl QuantityExpr {
+	fn evaluate<'a>(&self, _dict: &Dictionary<'a>) -> Result<Value<'a>, EvaluationError<'a>> {
+		Ok(Value::Quantity(self.quantity.clone()))
+	}
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	use super::*;
+	use crate::chem_data::elements::PeriodicTable;
+
+	/// Parses the string, which must be well formed, and evaluates it
+	pub(crate) fn evaluate<'a>(
+		dict: &Dictionary<'a>,
+		string: &str,
+	) -> Result<Value<'a>, EvaluationError<'a>> {
+		parse_str(string).unwrap().evaluate(dict)
+	}
+
+	#[test]
+	fn applies_precedence_and_associativity() {
+		let dict = Dictionary::new();
+		let cases = [
+			("1 + 2 * 3", 7),
+			("2 * 3 + 1", 7),
+			("10 - 4 - 3", 3),
+			("24 / 4 / 2", 3),
+			("2 ^ 3 ^ 2", 512),
+			("1 + 2 * 3 ^ 2 - 4", 15),
+		];
+		for (string, result) in cases {
+			assert_eq!(
+				evaluate(&dict, string).unwrap(),
+				Value::Integer(result),
+				"{}",
+				string
+			);
+		}
+	}
+
+	#[test]
+	fn dispatches_on_value_types() {
+		let p_table = PeriodicTable::new_alphabetic();
+		let mut dict = Dictionary::new();
+		dict.load_elements(&p_table);
+		let al = p_table.get_element("Al").unwrap();
+		let bo = p_table.get_element("Bo").unwrap();
+
+		let mut formula = MolecularFormula::new();
+		formula.set_subscr(al, 2);
+		formula.set_subscr(bo, 4);
+		assert_eq!(
+			evaluate(&dict, "$Al + $Bo2 * 2 + $Al").unwrap(),
+			Value::MolecularFormula(formula)
+		);
+
+		assert_eq!(
+			evaluate(&dict, "1.5 * 2.00").unwrap(),
+			Value::RealNumber(RealNumber::new(3.0, 2))
+		);
+		assert_eq!(
+			evaluate(&dict, "1.50 * 2").unwrap(),
+			Value::RealNumber(RealNumber::new(3.0, 3))
+		);
+		assert_eq!(
+			evaluate(&dict, "7 / 2").unwrap(),
+			Value::RealNumber(RealNumber::exact(3.5))
+		);
+		assert_eq!(
+			evaluate(&dict, "1 - 2").unwrap(),
+			Value::RealNumber(RealNumber::exact(-1.0))
+		);
+
+		assert_eq!(
+			evaluate(&dict, "$Al2Bo4 - $Bo2 * 2 - $Al")
+				.unwrap()
+				.to_string(),
+			"(molecular formula) Al"
+		);
+		assert!(matches!(
+			evaluate(&dict, "$Al - $Bo"),
+			Err(EvaluationError::Formula(
+				FormulaArithmeticError::NegativeSubscript { .. }
+			))
+		));
+		assert!(matches!(
+			evaluate(&dict, "$Al2147483647 * 2"),
+			Err(EvaluationError::Formula(FormulaArithmeticError::Overflow))
+		));
+
+		assert!(matches!(
+			evaluate(&dict, "1.0 / 0"),
+			Err(EvaluationError::DivisionByZero)
+		));
+		for string in ["(0 - 4.0) ^ 0.5", "0.0 ^ (0 - 1)", "0 ^ (0 - 2.5)"] {
+			assert!(
+				matches!(
+					evaluate(&dict, string),
+					Err(EvaluationError::UndefinedPower)
+				),
+				"{}",
+				string
+			);
+		}
+		assert_eq!(
+			evaluate(&dict, "(0 - 8.0) ^ 3").unwrap(),
+			Value::RealNumber(RealNumber::new(-512.0, 2))
+		);
+		for string in [
+			"2.0 ^ 2000",
+			"1.0e300 * 1.0e300",
+			"1.0e308 + 1.0e308",
+			"2 ^ 2000.0",
+		] {
+			assert!(
+				matches!(evaluate(&dict, string), Err(EvaluationError::Overflow)),
+				"{}",
+				string
+			);
+		}
+		assert!(matches!(
+			evaluate(&dict, "$Al + 1.5"),
+			Err(EvaluationError::BadType(BadTypeError::Operands {
+				operator: "+",
+				lhs_type: DataType::MolecularFormula,
+				rhs_type: DataType::RealNumber,
+			}))
+		));
+	}
+
+	#[test]
+	fn calls_methods() {
+		let p_table = PeriodicTable::new_alphabetic();
+		let mut dict = Dictionary::new();
+		dict.load_elements(&p_table);
+		let al = p_table.get_element("Al").unwrap();
+		let bo = p_table.get_element("Bo").unwrap();
+
+		let mut formula = MolecularFormula::new();
+		formula.set_subscr(al, 1);
+		formula.set_subscr(bo, 2);
+		assert_eq!(
+			evaluate(&dict, "$Al3Bo6.empirical").unwrap(),
+			Value::MolecularFormula(formula)
+		);
+		assert_eq!(
+			evaluate(&dict, "$Al3Bo6.atoms + 1").unwrap(),
+			Value::Integer(10)
+		);
+		assert_eq!(
+			evaluate(&dict, "Al.name").unwrap(),
+			Value::String("alicium".to_string())
+		);
+		assert_eq!(evaluate(&dict, "Bo.number * 2").unwrap(), Value::Integer(4));
+
+		let eqn = evaluate(&dict, "$Al + $Bo2 -> $Al2Bo3").unwrap();
+		dict.assign_value("eqn", eqn);
+		let balanced = evaluate(&dict, "eqn.balance").unwrap();
+		assert_eq!(
+			balanced.as_type::<ChemEqn>().unwrap().to_string(),
+			"4Al + 3Bo2 -> 2Al2Bo3"
+		);
+
+		match evaluate(&dict, "Al.mass") {
+			Err(EvaluationError::Method(MethodError::UnknownMethod { available, .. })) => {
+				assert_eq!(available, vec!["name", "number", "symbol"]);
+			},
+			other => panic!("Expected an unknown method error, got {:?}", other),
+		}
+	}
+
+	#[test]
+	fn calls_registered_methods() {
+		fn shout<'a>(
+			receiver: Value<'a>,
+			_args: Vec<Value<'a>>,
+		) -> Result<Value<'a>, MethodError<'a>> {
+			Ok(Value::String(receiver.as_type::<String>()?.to_uppercase()))
+		}
+
+		let mut dict = Dictionary::new();
+		dict.methods_mut()
+			.register(DataType::String, "shout", 0, shout);
+		assert_eq!(
+			evaluate(&dict, "\"hi\".shout").unwrap(),
+			Value::String("HI".to_string())
+		);
+	}
+
+	#[test]
+	fn evaluates_charges() {
+		let p_table = PeriodicTable::new_alphabetic();
+		let mut dict = Dictionary::new();
+		dict.load_elements(&p_table);
+		let charge_of = |string: &str| {
+			evaluate(&dict, string)
+				.unwrap()
+				.as_type::<MolecularFormula>()
+				.unwrap()
+				.get_charge()
+		};
+
+		assert_eq!(charge_of("$BoOs4-2"), -2);
+		assert_eq!(charge_of("$NcHe4+"), 1);
+		assert_eq!(charge_of("$Fr+3"), 3);
+		assert_eq!(charge_of("$Fr"), 0);
+		assert_eq!(charge_of("$e"), -1);
+		assert_eq!(charge_of("$NcHe4+ + $BoOs4-2"), -1);
+		assert_eq!(charge_of("$BoOs4-2 * 3"), -6);
+		assert_eq!(
+			evaluate(&dict, "$BoOs4-2").unwrap().to_string(),
+			"(molecular formula) BoOs4²⁻"
+		);
+
+		let eqn = evaluate(&dict, "$Fr+3 + $e -> $Fr")
+			.unwrap()
+			.as_type::<ChemEqn>()
+			.unwrap();
+		assert_eq!(eqn.balance().unwrap().to_string(), "Fr³⁺ + 3e⁻ -> Fr");
+	}
+
+	#[test]
+	fn evaluates_condensed_formulas() {
+		let p_table = PeriodicTable::new_alphabetic();
+		let mut dict = Dictionary::new();
+		dict.load_elements(&p_table);
+		let formula = |string: &str| {
+			evaluate(&dict, string)
+				.unwrap()
+				.as_type::<MolecularFormula>()
+				.unwrap()
+		};
+
+		let cases = [
+			("$$Ch(OsHe)2", "$ChOs2He2", "Ch(OsHe)2"),
+			("$$(NcHe4)3BoOs4", "$Nc3He12BoOs4", "(NcHe4)3BoOs4"),
+			("$$Fr2(BoOs4)3", "$Fr2Bo3Os12", "Fr2(BoOs4)3"),
+			("$$Al(Bo(Ch2)3)2", "$AlBo2Ch12", "Al(Bo(Ch2)3)2"),
+			("$$ChBoOs4.5He2Os", "$ChBoOs9He10", "ChBoOs4·5He2Os"),
+			("$$ChBoOs4·He2Os", "$ChBoOs5He2", "ChBoOs4·He2Os"),
+			("$$ChBo.2He2Os", "$ChBoHe4Os2", "ChBo·2He2Os"),
+			("$$Ch(OsHe)3.He2Os", "$ChOs4He5", "Ch(OsHe)3·He2Os"),
+			("$$Fr(BoOs4)2-", "$FrBo2Os8-", "Fr(BoOs4)2⁻"),
+		];
+		for (condensed, flattened, displayed) in cases {
+			assert_eq!(formula(condensed), formula(flattened), "{}", condensed);
+			assert_eq!(formula(condensed).to_string(), displayed);
+		}
+
+		assert_eq!(
+			evaluate(&dict, "$$Ch(OsHe)2.atoms").unwrap(),
+			Value::Integer(5)
+		);
+		assert_eq!(
+			evaluate(&dict, "$$Ch.He2Os.atoms * 2").unwrap(),
+			Value::Integer(8)
+		);
+		assert_eq!(
+			evaluate(&dict, "$$Ch(OsHe)2.count(Os)").unwrap(),
+			Value::Integer(2)
+		);
+		assert_eq!(
+			evaluate(&dict, "2$$Ch(OsHe)2.atoms").unwrap(),
+			Value::Integer(10)
+		);
+		assert_eq!(
+			evaluate(&dict, "count($$Ch(OsHe)2, Os)").unwrap(),
+			Value::Integer(2)
+		);
+		let eqn = evaluate(&dict, "$$Ch(OsHe)2 -> $Ch + $Os + $He2").unwrap();
+		assert_eq!(
+			eqn.as_type::<ChemEqn>()
+				.unwrap()
+				.balance()
+				.unwrap()
+				.to_string(),
+			"Ch(OsHe)2 -> Ch + He2 + 2Os"
+		);
+		for malformed in ["$$Ch(OsHe", "$$Ch()", "$$2Ch", "$$Ch·", "$$Ch.", "$$Ch.*2"] {
+			assert!(parse_str(malformed).is_err(), "{}", malformed);
+		}
+	}
+
+	#[test]
+	fn evaluates_isotopes() {
+		let p_table = PeriodicTable::standard();
+		let mut dict = Dictionary::new();
+		dict.load_elements(&p_table);
+		let formula = |string: &str| {
+			evaluate(&dict, string)
+				.unwrap()
+				.as_type::<MolecularFormula>()
+				.unwrap()
+		};
+
+		assert_eq!(formula("$[13]CH4").to_string(), "¹³CH4");
+		assert_ne!(formula("$[13]CH4"), formula("$CH4"));
+		assert_eq!(formula("$D2O").to_string(), "D2O");
+		assert_eq!(formula("$D2O"), formula("$[2]H2O"));
+		assert_eq!(
+			formula("$$[13]CH3(CH2)2[13]CH3").to_string(),
+			"¹³CH3(CH2)2¹³CH3"
+		);
+		assert_eq!(formula("$$[13]CH3(CH2)2[13]CH3"), formula("$[13]C2H10C2"));
+		assert_eq!(
+			evaluate(&dict, "[13]C.name").unwrap(),
+			Value::String("carbon-13".to_string())
+		);
+		assert!(matches!(
+			evaluate(&dict, "$[99]C"),
+			Err(EvaluationError::UnknownIsotope {
+				mass_number: 99,
+				..
+			})
+		));
+
+		let eqn = evaluate(&dict, "$[13]CH4 + $O2 -> $[13]CO2 + $H2O").unwrap();
+		assert_eq!(
+			eqn.as_type::<ChemEqn>()
+				.unwrap()
+				.balance()
+				.unwrap()
+				.to_string(),
+			"¹³CH4 + 2O2 -> ¹³CO2 + 2H2O"
+		);
+		let eqn = evaluate(&dict, "$CH4 + $O2 -> $[13]CO2 + $H2O").unwrap();
+		assert!(eqn.as_type::<ChemEqn>().unwrap().balance().is_err());
+	}
+
+	#[test]
+	fn evaluates_molar_masses() {
+		let p_table = PeriodicTable::standard();
+		let mut dict = Dictionary::new();
+		dict.load_elements(&p_table);
+		let mass = |string: &str| {
+			evaluate(&dict, string)
+				.unwrap()
+				.as_type::<RealNumber>()
+				.unwrap()
+		};
+
+		assert_eq!(mass("$H2O.mass").to_string(), "18.015");
+		assert_eq!(
+			mass("$$Ca(OH)2.mass").to_string(),
+			mass("$CaO2H2.mass").to_string()
+		);
+		assert_eq!(mass("$D2O.mass").to_string(), "20.027");
+		assert!(mass("$H2O.mass").uncertainty().is_some());
+		assert!(mass("$[13]CH4.mass").value() > mass("$CH4.mass").value());
+
+		let mut p_table = PeriodicTable::default();
+		p_table
+			.add_element(Element::new(1, "Al", "alicium"))
+			.unwrap();
+		let mut dict = Dictionary::new();
+		dict.load_elements(&p_table);
+		assert!(matches!(
+			evaluate(&dict, "$Al2.mass"),
+			Err(EvaluationError::Method(MethodError::MolarMass(_)))
+		));
+	}
+
+	#[test]
+	fn sorts_formulas() {
+		let p_table = PeriodicTable::standard();
+		let mut dict = Dictionary::new();
+		dict.load_elements(&p_table);
+		let formula = |string: &str| {
+			evaluate(&dict, string)
+				.unwrap()
+				.as_type::<MolecularFormula>()
+				.unwrap()
+				.to_string()
+		};
+
+		assert_eq!(formula("$HCH3"), "H4C");
+		assert_eq!(formula("$HCH3.hill"), "CH4");
+		assert_eq!(formula("$$CH3(CH2)2OH.hill"), "C3H8O");
+		assert_eq!(formula("$NaCl.sorted(\"atomic_number\")"), "NaCl");
+		assert_eq!(formula("$NaCl.sorted(\"alphabetical\")"), "ClNa");
+		assert!(matches!(
+			evaluate(&dict, "$NaCl.sorted(\"random\")"),
+			Err(EvaluationError::Method(MethodError::Other(_)))
+		));
+	}
+
+	#[test]
+	fn derives_empirical_formulas() {
+		let p_table = PeriodicTable::standard();
+		let mut dict = Dictionary::new();
+		dict.load_elements(&p_table);
+		let formula = |string: &str| {
+			evaluate(&dict, string)
+				.unwrap()
+				.as_type::<MolecularFormula>()
+				.unwrap()
+				.to_string()
+		};
+
+		let percents = evaluate(&dict, "$H2O.percents")
+			.unwrap()
+			.as_type::<Vec<Value>>()
+			.unwrap();
+		assert_eq!(percents.len(), 2);
+		let hydrogen = percents[0].clone().as_type::<Vec<Value>>().unwrap();
+		assert_eq!(
+			hydrogen[0],
+			Value::ElementRef(p_table.get_element("H").unwrap())
+		);
+		assert_eq!(
+			hydrogen[1]
+				.clone()
+				.as_type::<RealNumber>()
+				.unwrap()
+				.to_string(),
+			"11.19"
+		);
+
+		assert_eq!(
+			formula("((C, 40.0), (H, 6.7), (O, 53.3)).empirical"),
+			"CH2O"
+		);
+		// an Fe:O ratio of 1:1.5 and a C:H ratio of 1:1.33
+		assert_eq!(formula("((Fe, 69.94), (O, 30.06)).empirical"), "Fe2O3");
+		assert_eq!(formula("((C, 0.8994), (H, 0.1006)).empirical"), "C3H4");
+		assert_eq!(formula("((Na, 23), (Cl, 35)).empirical"), "NaCl");
+		assert!(matches!(
+			evaluate(&dict, "((C, 1.0), (H, 0.0)).empirical"),
+			Err(EvaluationError::Method(MethodError::EmpiricalFormula(_)))
+		));
+		assert!(matches!(
+			evaluate(&dict, "((C, 1.0, 2.0),).empirical"),
+			Err(EvaluationError::Method(MethodError::Other(_)))
+		));
+	}
+
+	#[test]
+	fn evaluates_tuples() {
+		let dict = Dictionary::new();
+		assert_eq!(
+			evaluate(&dict, "(1, 2 + 3, \"a\")").unwrap(),
+			Value::Tuple(vec![
+				Value::Integer(1),
+				Value::Integer(5),
+				Value::String("a".to_string())
+			])
+		);
+		assert_eq!(evaluate(&dict, "(1 + 2) * 3").unwrap(), Value::Integer(9));
+		assert_eq!(
+			evaluate(&dict, "(1,)").unwrap(),
+			Value::Tuple(vec![Value::Integer(1)])
+		);
+		assert_eq!(evaluate(&dict, "()").unwrap(), Value::Tuple(Vec::new()));
+		assert_eq!(
+			evaluate(&dict, "((1, 2), 3).len").unwrap(),
+			Value::Integer(2)
+		);
+		assert_eq!(
+			evaluate(&dict, "(1, (2,), ())").unwrap().to_string(),
+			"(tuple) (1, (2,), ())"
+		);
+	}
+
+	#[test]
+	fn evaluates_repeatedly() {
+		let alphabetic = PeriodicTable::new_alphabetic();
+		let standard = PeriodicTable::standard();
+		let mut dicts = [Dictionary::new(), Dictionary::new()];
+		dicts[0].load_elements(&alphabetic);
+		dicts[1].load_elements(&standard);
+
+		let expr = parse_str("count($$Fr(OsHe)2, Os) * n").unwrap();
+		for (n, dict) in dicts.iter_mut().enumerate() {
+			let n = n as u32 + 1;
+			dict.assign_value("n", Value::Integer(n));
+			assert_eq!(expr.evaluate(dict).unwrap(), Value::Integer(2 * n));
+			// evaluating again gives the same value
+			assert_eq!(expr.evaluate(dict).unwrap(), Value::Integer(2 * n));
+		}
+
+		let eqn = parse_str("$Fr+3 + $e -> $Fr").unwrap();
+		let balanced = |dict: &Dictionary| {
+			let eqn = eqn.evaluate(dict).unwrap().as_type::<ChemEqn>().unwrap();
+			eqn.balance().unwrap().to_string()
+		};
+		assert_eq!(balanced(&dicts[0]), "Fr³⁺ + 3e⁻ -> Fr");
+		assert_eq!(balanced(&dicts[1]), balanced(&dicts[0]));
+	}
+
+	#[test]
+	fn walks_expressions() {
+		// method names aren't expressions, but the arguments of a method call are
+		let expr = parse_str("count($$Ca(OH)2, O) + [13]C.mass * $SO4-2.count(S)").unwrap();
+		let mut identifiers = Vec::new();
+		expr.walk(&mut |expr| {
+			if let Expression::Identifier(identifier) = expr {
+				identifiers.push(identifier.name.as_str());
+			}
+		});
+		assert_eq!(identifiers, ["Ca", "O", "H", "O", "C", "S", "O", "S"]);
+
+		let (exprs, _) = parse_script("$H2O\n(1, 2$O2)\n$$CuSO4.5H2O -> $CuSO4 + 5$H2O");
+		let mut formulas = 0;
+		for expr in &exprs {
+			expr.walk(&mut |expr| {
+				if matches!(
+					expr,
+					Expression::MolecularFormula(_) | Expression::CondensedFormula(_)
+				) {
+					formulas += 1;
+				}
+			});
+		}
+		assert_eq!(formulas, 5);
+
+		match parse_str("(1, \"a\")").unwrap() {
+			Expression::Tuple(TupleExpr { values }) => assert_eq!(
+				values,
+				[
+					Expression::Integer(IntegerLiteral { value: 1 }),
+					Expression::String(StringLiteral {
+						content: "a".to_string()
+					}),
+				]
+			),
+			other => panic!("Expected a tuple, got {:?}", other),
+		}
+	}
+
+	#[test]
+	fn rewrites_expressions() {
+		let p_table = PeriodicTable::new_alphabetic();
+		let mut dict = Dictionary::new();
+		dict.load_elements(&p_table);
+
+		// swap every Al for Bo
+		let mut expr = parse_str("$$Al(OsAl)2 + 2$Al").unwrap();
+		expr.walk_mut(&mut |expr| {
+			if let Expression::Identifier(identifier) = expr {
+				if identifier.name == "Al" {
+					identifier.name = "Bo".to_string();
+				}
+			}
+		});
+		assert_eq!(expr, parse_str("$$Bo(OsBo)2 + 2$Bo").unwrap());
+		assert_eq!(
+			expr.evaluate(&dict).unwrap(),
+			evaluate(&dict, "$Bo5Os2").unwrap()
+		);
+
+		// replace a whole subexpression
+		let mut expr = parse_str("(1 + 2) * 3").unwrap();
+		for child in expr.children_mut() {
+			if matches!(child, Expression::Infix(_)) {
+				*child = Expression::Integer(IntegerLiteral { value: 4 });
+			}
+		}
+		assert_eq!(expr.evaluate(&dict).unwrap(), Value::Integer(12));
+	}
+
+	#[test]
+	fn calls_methods_with_arguments() {
+		fn react_with<'a>(
+			receiver: Value<'a>,
+			args: Vec<Value<'a>>,
+		) -> Result<Value<'a>, MethodError<'a>> {
+			let mut eqn = ChemEqn::new();
+			let mut add_reactant = |species: Value<'a>| -> Result<(), MethodError<'a>> {
+				eqn.add_species(species.as_type()?, -1)
+					.map_err(|err| MethodError::Other(Box::new(err)))
+			};
+			add_reactant(receiver)?;
+			for arg in args {
+				add_reactant(arg)?;
+			}
+			Ok(Value::ChemEqn(eqn))
+		}
+
+		let p_table = PeriodicTable::new_alphabetic();
+		let mut dict = Dictionary::new();
+		dict.load_elements(&p_table);
+		dict.methods_mut()
+			.register(DataType::MolecularFormula, "react_with", 1, react_with);
+
+		assert_eq!(
+			evaluate(&dict, "$Al2Bo3.count(Bo)").unwrap(),
+			Value::Integer(3)
+		);
+		assert_eq!(
+			evaluate(&dict, "$Al2Bo3.count(Bo) * 2").unwrap(),
+			Value::Integer(6)
+		);
+		assert_eq!(
+			evaluate(&dict, "count($Al2Bo3, Al)").unwrap(),
+			Value::Integer(2)
+		);
+		assert_eq!(
+			evaluate(&dict, "(4, 5, 6).get(1)").unwrap(),
+			Value::Integer(5)
+		);
+		let eqn = evaluate(&dict, "$Al2Bo3.react_with($Al)").unwrap();
+		assert_eq!(eqn.as_type::<ChemEqn>().unwrap().iter().count(), 2);
 
-	fn forehead(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		self.fmt(f)
+		assert!(matches!(
+			evaluate(&dict, "$Al2Bo3.count(Al, Bo)"),
+			Err(EvaluationError::Method(MethodError::ArgumentCount {
+				expected: 1,
+				found: 2,
+				..
+			}))
+		));
+		assert!(matches!(
+			evaluate(&dict, "count()"),
+			Err(EvaluationError::ExpectedReceiver { .. })
+		));
 	}
 }
@@ -1,32 +1,181 @@
 use super::*;
-use crate::parse::tokens::{IntoTokenIter, Token, Tokens};
+use crate::parse::{
+	span::{line_and_column, underline, Span},
+	tokens::{IntoTokenIter, SpannedToken, Token, Tokens},
+};
+use std::{error::Error, fmt::Display, iter::Peekable};
 
-pub fn parse_str(string: &str) -> Result<Box<dyn Expression>, ParseError> {
+mod special_syntax;
+
+pub use special_syntax::{register_special_syntax, SpecialSyntax};
+
+pub fn parse_str(string: &str) -> Result<Expression, ParseError> {
 	parse_tokens(string.chars().into_token_iter())
 }
 
+/// Parses the tokens as a single expression, stopping at the first error
 pub fn parse_tokens<I: Iterator<Item = char>>(
 	token_iter: Tokens<I>,
-) -> Result<Box<dyn Expression>, ParseError> {
+) -> Result<Expression, ParseError> {
+	let (expr, errors) = parse_tokens_recovering(token_iter);
+	match errors.into_iter().next() {
+		None => Ok(expr),
+		Some(error) => Err(error),
+	}
+}
+
+pub fn parse_str_recovering(string: &str) -> (Expression, Vec<ParseError>) {
+	parse_tokens_recovering(string.chars().into_token_iter())
+}
+
+/// Parses the tokens as a single expression, continuing past errors to report all of them. Parsing
+/// resumes at the next operator of an infix operation, the `,` or `)` ending a tuple element or the
+/// `}` ending special syntax, with the malformed part replaced by an error placeholder; an error
+/// that none of these encloses makes the whole expression a placeholder. The errors are in the
+/// order they were found.
+pub fn parse_tokens_recovering<I: Iterator<Item = char>>(
+	mut token_iter: Tokens<I>,
+) -> (Expression, Vec<ParseError>) {
+	let mut errors = Vec::new();
+	let expr = parse_expression_recovering(&mut token_iter, false, &mut errors)
+		.expect("An expression should've been parsed when not stopping at line breaks");
+	(expr, errors)
+}
+
+/// Parses each expression of the source, continuing past errors as `parse_tokens_recovering` does.
+/// A line break ends an expression unless it is inside brackets, so an expression may span lines,
+/// e.g. `(1,\n2)`. An error that nothing inside the expression recovers from skips to the end of
+/// its line.
+pub fn parse_script(source: &str) -> (Vec<Expression>, Vec<ParseError>) {
+	let mut token_iter = source.chars().into_token_iter();
+	let mut exprs = Vec::new();
+	let mut errors = Vec::new();
+	while let Some(expr) = parse_expression_recovering(&mut token_iter, true, &mut errors) {
+		exprs.push(expr);
+	}
+	(exprs, errors)
+}
+
+/// Parses one expression from the tokens, recording its errors in `errors`. If `stop_at_newline`,
+/// the expression ends at the first line break outside of any brackets, blank lines before it are
+/// skipped, and None is returned if there was nothing left but whitespace. Otherwise line breaks
+/// are just whitespace and the expression takes in all of the tokens.
+fn parse_expression_recovering<I: Iterator<Item = char>>(
+	token_iter: &mut Tokens<I>,
+	stop_at_newline: bool,
+	errors: &mut Vec<ParseError>,
+) -> Option<Expression> {
 	let mut result = Box::new(WrapperExprBuilder::new());
-	for token in token_iter {
-		match result.add_token(token)? {
-			None => (),
-			Some(rejected) => return Err(ParseError::UnexpectedToken(rejected)),
+	let mut failure = None;
+	let mut depth: usize = 0;
+	let mut is_blank = true;
+	let mut end = None;
+	for SpannedToken { token, span } in token_iter.by_ref() {
+		let token = match token {
+			Token::Newline if stop_at_newline && depth == 0 => {
+				if is_blank {
+					continue;
+				}
+				end = Some(Span::at(span.start));
+				break;
+			},
+			Token::Newline => Token::Whitespace,
+			token => token,
+		};
+		match token {
+			Token::Whitespace => (),
+			Token::LParen | Token::LBrack | Token::LCurly => depth += 1,
+			Token::RParen | Token::RBrack | Token::RCurly => depth = depth.saturating_sub(1),
+			_ => (),
+		}
+		is_blank &= token == Token::Whitespace;
+		if failure.is_some() {
+			// skip the rest of the expression
+			continue;
+		}
+		match result.add_token(token, span, errors) {
+			Ok(None) => (),
+			Ok(Some(rejected)) => failure = Some(ParseError::UnexpectedToken(rejected, span)),
+			Err(error) => failure = Some(error),
 		}
 	}
-	Ok(result.finish()?)
+	if stop_at_newline && is_blank {
+		return None;
+	}
+	let end = end.unwrap_or_else(|| Span::at(Tokens::position(token_iter)));
+	let expr = match failure {
+		None => result.finish(end, errors),
+		Some(error) => Err(error),
+	};
+	Some(expr.unwrap_or_else(|error| {
+		errors.push(error.clone());
+		Expression::Error(ErrorExpr { error })
+	}))
 }
 
-#[derive(Debug)]
+/// An error along with the span of the source where it occurred
+#[derive(Debug, Clone, PartialEq)]
 pub enum ParseError {
-	NoTokens,
-	UnexpectedToken(Token),
-	ExpectedTokens,
-	RequiredParsingTimeIdentifier,
+	/// There was nothing but whitespace to parse
+	NoTokens(Span),
+	UnexpectedToken(Token, Span),
+	/// The input ended before the expression was complete
+	ExpectedTokens(Span),
+	/// A `name!{...}` block whose name isn't a registered special syntax
+	UnknownSpecialSyntax(String, Span),
+	/// What is between the braces of a special syntax block is malformed, e.g. an invalid SMILES
+	/// string; the message describes how
+	InvalidSpecialSyntax(String, Span),
+}
+
+impl ParseError {
+	pub fn span(&self) -> Span {
+		match self {
+			ParseError::NoTokens(span)
+			| ParseError::UnexpectedToken(_, span)
+			| ParseError::ExpectedTokens(span)
+			| ParseError::UnknownSpecialSyntax(_, span)
+			| ParseError::InvalidSpecialSyntax(_, span) => *span,
+		}
+	}
+
+	/// Describes the error along with the line of the source that it occurred in, underlining
+	/// where it occurred, e.g.
+	/// ```text
+	/// Line 1, column 12: Unexpected token '->'
+	/// 1 | $Al -> $Bo -> $Ch
+	///   |            ^^
+	/// ```
+	pub fn render(&self, source: &str) -> String {
+		let (line, column) = line_and_column(source, self.span().start);
+		format!(
+			"Line {}, column {}: {}\n{}",
+			line,
+			column,
+			self,
+			underline(source, self.span())
+		)
+	}
+}
+impl Display for ParseError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			ParseError::NoTokens(_) => write!(f, "Expected an expression"),
+			ParseError::UnexpectedToken(token, _) => write!(f, "Unexpected token '{}'", token),
+			ParseError::ExpectedTokens(_) => write!(f, "Unexpected end of input"),
+			ParseError::UnknownSpecialSyntax(name, _) => {
+				write!(f, "Unknown special syntax '{}!'", name)
+			},
+			ParseError::InvalidSpecialSyntax(message, _) => write!(f, "{}", message),
+		}
+	}
 }
+impl Error for ParseError {}
 
-fn create_new_expression(first_token: Token) -> Result<Box<dyn ExpressionBuilder>, ParseError> {
+fn create_new_expression(
+	first_token: Token,
+	span: Span,
+) -> Result<Box<dyn ExpressionBuilder>, ParseError> {
 	match first_token {
 		Token::Identifier(name) => Ok(Box::new(IdentifierExprBuilder::new(name))),
 		Token::StringLiteral(content) => Ok(Box::new(StringLiteralExprBuilder::new(content))),
@@ -37,9 +186,10 @@ fn create_new_expression(first_token: Token) -> Result<Box<dyn ExpressionBuilder
 		))),
 		Token::Real(value) => Ok(Box::new(RealLiteralExprBuilder::new(value))),
 		Token::LParen => Ok(Box::new(TupleExprBuilder::new())),
+		Token::LBrack => Ok(Box::new(IsotopeExprBuilder::new())),
 		Token::Cash => Ok(Box::new(MolecularFormulaExprBuilder::new())),
 		Token::CashCash => Ok(Box::new(CondensedFormulaExprBuilder::new())),
-		_ => Err(ParseError::UnexpectedToken(first_token)),
+		_ => Err(ParseError::UnexpectedToken(first_token, span)),
 	}
 }
 
@@ -57,17 +207,46 @@ fn create_new_expression(first_token: Token) -> Result<Box<dyn ExpressionBuilder
 /// ExpressionBuilder, or the token cannot be rejected because some expression is not yet in a valid
 /// state (at which point a ParseError is propagated). Any one ExpressionBuilder should never be
 /// given Tokens again after it has rejected one (and thereby "closed" itself); to do otherwise is a
-/// logic error.
-trait ExpressionBuilder {
+/// logic error. Line breaks are given as Whitespace.
+///
+/// A ParseError propagates only up to the nearest infix operation, tuple or special syntax, which
+/// records it in the `errors` passed down with each token, replaces the malformed part with a placeholder, and keeps
+/// going so that later errors are found too.
+pub trait ExpressionBuilder {
 	/// Attempts to incorporate the specified token into the current ExpressionBuilder. If
 	/// incorporation is unsuccessful, the token will either be rejected (if the current
 	/// ExpressionBuilder is in a valid state) or a ParseError will be returned.
-	fn add_token(&mut self, token: Token) -> Result<Option<Token>, ParseError>;
+	fn add_token(
+		&mut self,
+		token: Token,
+		span: Span,
+		errors: &mut Vec<ParseError>,
+	) -> Result<Option<Token>, ParseError>;
+
+	/// Finishes building the expression; `end` is where the expression ended, which is where an
+	/// incomplete expression is reported.
+	fn finish(
+		self: Box<Self>,
+		end: Span,
+		errors: &mut Vec<ParseError>,
+	) -> Result<Expression, ParseError>;
+
+	fn parsing_time_identifier(&self) -> Option<&str> {
+		None
+	}
 
-	fn finish(self: Box<Self>) -> Result<Box<dyn Expression>, ParseError>;
+	fn parsing_time_integer(&self) -> Option<u32> {
+		None
+	}
 
-	fn parsing_time_identifier(&self) -> Result<&str, ParseError> {
-		Err(ParseError::RequiredParsingTimeIdentifier)
+	/// Finishes the expression as one side of a chemical equation, i.e. a sum of species that each
+	/// may have a leading coefficient. By default the whole expression is a single species.
+	fn into_equation_side(
+		self: Box<Self>,
+		end: Span,
+		errors: &mut Vec<ParseError>,
+	) -> Result<EquationSide, ParseError> {
+		Ok(vec![(1, self.finish(end, errors)?)])
 	}
 }
 
@@ -84,14 +263,19 @@ impl WrapperExprBuilder {
 }
 
 impl ExpressionBuilder for WrapperExprBuilder {
-	fn add_token(&mut self, token: Token) -> Result<Option<Token>, ParseError> {
+	fn add_token(
+		&mut self,
+		token: Token,
+		span: Span,
+		errors: &mut Vec<ParseError>,
+	) -> Result<Option<Token>, ParseError> {
 		match &mut self.maybe_inner_expr {
 			None => {
 				if !matches!(token, Token::Whitespace) {
-					self.maybe_inner_expr = Some(create_new_expression(token)?);
+					self.maybe_inner_expr = Some(create_new_expression(token, span)?);
 				}
 			},
-			Some(expr) => match expr.add_token(token)? {
+			Some(expr) => match expr.add_token(token, span, errors)? {
 				None => (),
 				Some(Token::Whitespace) => (),
 				Some(rejected) => {
@@ -100,6 +284,7 @@ impl ExpressionBuilder for WrapperExprBuilder {
 							.take()
 							.expect("There should've been an inner expr"),
 						rejected,
+						span,
 					);
 					self.maybe_inner_expr = Some(expr);
 					if let Some(bad_token) = maybe_rejected {
@@ -113,13 +298,20 @@ impl ExpressionBuilder for WrapperExprBuilder {
 		Ok(None)
 	}
 
-	fn finish(self: Box<Self>) -> Result<Box<dyn Expression>, ParseError> {
-		self.maybe_inner_expr.ok_or(ParseError::NoTokens)?.finish()
+	fn finish(
+		self: Box<Self>,
+		end: Span,
+		errors: &mut Vec<ParseError>,
+	) -> Result<Expression, ParseError> {
+		self.maybe_inner_expr
+			.ok_or(ParseError::NoTokens(end))?
+			.finish(end, errors)
 	}
 }
 
 /// Represents an expression being built that currently only has an identifier, e.g.
 /// `identifier`
+/// `snake_case_identifier`
 struct IdentifierExprBuilder {
 	identifier: String,
 }
@@ -131,19 +323,86 @@ impl IdentifierExprBuilder {
 }
 
 impl ExpressionBuilder for IdentifierExprBuilder {
-	fn add_token(&mut self, token: Token) -> Result<Option<Token>, ParseError> {
+	fn add_token(
+		&mut self,
+		token: Token,
+		_span: Span,
+		_errors: &mut Vec<ParseError>,
+	) -> Result<Option<Token>, ParseError> {
 		// always closed
 		Ok(Some(token))
 	}
 
-	fn finish(self: Box<Self>) -> Result<Box<dyn Expression>, ParseError> {
-		Ok(Box::new(Identifier {
+	fn finish(
+		self: Box<Self>,
+		_end: Span,
+		_errors: &mut Vec<ParseError>,
+	) -> Result<Expression, ParseError> {
+		Ok(Expression::Identifier(Identifier {
 			name: self.identifier,
 		}))
 	}
 
-	fn parsing_time_identifier(&self) -> Result<&str, ParseError> {
-		Ok(&self.identifier)
+	fn parsing_time_identifier(&self) -> Option<&str> {
+		Some(&self.identifier)
+	}
+}
+
+/// Represents an isotope being built, e.g.
+/// `[13`
+/// `[13]`
+/// `[13]C`
+struct IsotopeExprBuilder {
+	mass_number: Option<u32>,
+	closed_bracket: bool,
+	symbol: Option<String>,
+}
+
+impl IsotopeExprBuilder {
+	fn new() -> Self {
+		Self {
+			mass_number: None,
+			closed_bracket: false,
+			symbol: None,
+		}
+	}
+}
+
+impl ExpressionBuilder for IsotopeExprBuilder {
+	fn add_token(
+		&mut self,
+		token: Token,
+		span: Span,
+		_errors: &mut Vec<ParseError>,
+	) -> Result<Option<Token>, ParseError> {
+		match (&self.mass_number, self.closed_bracket, &self.symbol, token) {
+			(None, _, _, Token::Integer(digits)) => {
+				let mass_number = digits
+					.parse()
+					.map_err(|_| ParseError::UnexpectedToken(Token::Integer(digits), span))?;
+				self.mass_number = Some(mass_number);
+			},
+			(Some(_), false, _, Token::RBrack) => self.closed_bracket = true,
+			(_, true, None, Token::Identifier(symbol)) => self.symbol = Some(symbol),
+			// the symbol ends the isotope
+			(_, _, Some(_), token) => return Ok(Some(token)),
+			(_, _, None, token) => return Err(ParseError::UnexpectedToken(token, span)),
+		}
+		Ok(None)
+	}
+
+	fn finish(
+		self: Box<Self>,
+		end: Span,
+		_errors: &mut Vec<ParseError>,
+	) -> Result<Expression, ParseError> {
+		match (self.mass_number, self.symbol) {
+			(Some(mass_number), Some(name)) => Ok(Expression::Isotope(IsotopeExpr {
+				mass_number,
+				element: Box::new(Expression::Identifier(Identifier { name })),
+			})),
+			_ => Err(ParseError::ExpectedTokens(end)),
+		}
 	}
 }
 
@@ -160,13 +419,22 @@ impl StringLiteralExprBuilder {
 }
 
 impl ExpressionBuilder for StringLiteralExprBuilder {
-	fn add_token(&mut self, token: Token) -> Result<Option<Token>, ParseError> {
+	fn add_token(
+		&mut self,
+		token: Token,
+		_span: Span,
+		_errors: &mut Vec<ParseError>,
+	) -> Result<Option<Token>, ParseError> {
 		// always closed
 		Ok(Some(token))
 	}
 
-	fn finish(self: Box<Self>) -> Result<Box<dyn Expression>, ParseError> {
-		Ok(Box::new(StringLiteral {
+	fn finish(
+		self: Box<Self>,
+		_end: Span,
+		_errors: &mut Vec<ParseError>,
+	) -> Result<Expression, ParseError> {
+		Ok(Expression::String(StringLiteral {
 			content: self.content,
 		}))
 	}
@@ -185,13 +453,26 @@ impl IntegerLiteralExprBuilder {
 }
 
 impl ExpressionBuilder for IntegerLiteralExprBuilder {
-	fn add_token(&mut self, token: Token) -> Result<Option<Token>, ParseError> {
+	fn add_token(
+		&mut self,
+		token: Token,
+		_span: Span,
+		_errors: &mut Vec<ParseError>,
+	) -> Result<Option<Token>, ParseError> {
 		// always closed
 		Ok(Some(token))
 	}
 
-	fn finish(self: Box<Self>) -> Result<Box<dyn Expression>, ParseError> {
-		Ok(Box::new(IntegerLiteral { value: self.value }))
+	fn finish(
+		self: Box<Self>,
+		_end: Span,
+		_errors: &mut Vec<ParseError>,
+	) -> Result<Expression, ParseError> {
+		Ok(Expression::Integer(IntegerLiteral { value: self.value }))
+	}
+
+	fn parsing_time_integer(&self) -> Option<u32> {
+		Some(self.value)
 	}
 }
 
@@ -208,12 +489,21 @@ impl RealLiteralExprBuilder {
 }
 
 impl ExpressionBuilder for RealLiteralExprBuilder {
-	fn add_token(&mut self, token: Token) -> Result<Option<Token>, ParseError> {
+	fn add_token(
+		&mut self,
+		token: Token,
+		_span: Span,
+		_errors: &mut Vec<ParseError>,
+	) -> Result<Option<Token>, ParseError> {
 		Ok(Some(token))
 	}
 
-	fn finish(self: Box<Self>) -> Result<Box<dyn Expression>, ParseError> {
-		Ok(Box::new(RealLiteral { value: self.value }))
+	fn finish(
+		self: Box<Self>,
+		_end: Span,
+		_errors: &mut Vec<ParseError>,
+	) -> Result<Expression, ParseError> {
+		Ok(Expression::Real(RealLiteral { value: self.value }))
 	}
 }
 
@@ -227,6 +517,7 @@ impl ExpressionBuilder for RealLiteralExprBuilder {
 struct TupleExprBuilder {
 	values: Vec<Box<dyn ExpressionBuilder>>,
 	has_active_expr: bool,
+	seen_comma: bool,
 	closed: bool,
 }
 
@@ -235,26 +526,67 @@ impl TupleExprBuilder {
 		Self {
 			values: Vec::new(),
 			has_active_expr: false,
+			seen_comma: false,
 			closed: false,
 		}
 	}
 
+	/// Finishes each of the values in the tuple. A value that fails to finish is recorded in
+	/// `errors` and replaced by a placeholder, as is a missing `)`.
+	fn finish_values(self, end: Span, errors: &mut Vec<ParseError>) -> Vec<Expression> {
+		let mut values = Vec::new();
+		for expr_builder in self.values.into_iter() {
+			values.push(expr_builder.finish(end, errors).unwrap_or_else(|error| {
+				errors.push(error.clone());
+				Expression::Error(ErrorExpr { error })
+			}));
+		}
+		if !self.closed {
+			errors.push(ParseError::ExpectedTokens(end));
+		}
+		values
+	}
+
+	/// Records the error and replaces the active expression with a placeholder that skips ahead to
+	/// the next `,` or `)` of this tuple
+	fn recover_from(&mut self, error: ParseError, errors: &mut Vec<ParseError>) {
+		errors.push(error.clone());
+		if self.has_active_expr {
+			self.values.pop();
+		}
+		self.values.push(Box::new(ErrorExprBuilder::new(
+			error,
+			&[Token::Comma, Token::RParen],
+		)));
+		self.has_active_expr = true;
+	}
+
 	/// Attempts to add the token to the current active expression, or if none exists, to use the
 	/// token to create a new expression that will become the current active expression. If the
 	/// current active expression rejects the token, this method will return that; if the token is
 	/// used to create an expression but this fails, this method propagates the error.
-	fn add_to_active_expr(&mut self, token: Token) -> Result<Option<Token>, ParseError> {
+	fn add_to_active_expr(
+		&mut self,
+		token: Token,
+		span: Span,
+		errors: &mut Vec<ParseError>,
+	) -> Result<Option<Token>, ParseError> {
 		assert!(!self.closed);
 
 		if self.has_active_expr {
 			self.values
 				.last_mut()
 				.expect("There should've been an active expression")
-				.add_token(token)
+				.add_token(token, span, errors)
 		} else {
-			if !matches!(token, Token::Whitespace) {
-				self.values.push(create_new_expression(token)?);
-				self.has_active_expr = true;
+			match token {
+				Token::Whitespace => (),
+				// the tuple is empty or has a trailing comma, e.g. `()` or `(a,)`
+				Token::RParen => return Ok(Some(token)),
+				_ => {
+					self.values.push(create_new_expression(token, span)?);
+					self.has_active_expr = true;
+				},
 			}
 			Ok(None)
 		}
@@ -262,16 +594,32 @@ impl TupleExprBuilder {
 }
 
 impl ExpressionBuilder for TupleExprBuilder {
-	fn add_token(&mut self, token: Token) -> Result<Option<Token>, ParseError> {
+	fn add_token(
+		&mut self,
+		token: Token,
+		span: Span,
+		errors: &mut Vec<ParseError>,
+	) -> Result<Option<Token>, ParseError> {
 		if self.closed {
 			return Ok(Some(token));
 		}
 
-		match self.add_to_active_expr(token)? {
+		let rejected = match self.add_to_active_expr(token.clone(), span, errors) {
+			Ok(rejected) => rejected,
+			Err(error) => {
+				// skip the rest of the malformed value, starting with the token that broke it
+				self.recover_from(error, errors);
+				return self.add_token(token, span, errors);
+			},
+		};
+		match rejected {
 			None => (),
 			Some(Token::Whitespace) => (),
 			Some(Token::RParen) => self.closed = true,
-			Some(Token::Comma) => self.has_active_expr = false,
+			Some(Token::Comma) => {
+				self.has_active_expr = false;
+				self.seen_comma = true;
+			},
 			Some(rejected) => {
 				// attempt to create an infix expression with the rejected token
 				let (expr, maybe_rejected) = wrap_in_infix(
@@ -279,25 +627,73 @@ impl ExpressionBuilder for TupleExprBuilder {
 						.pop()
 						.expect("There should've been an active subexpression"),
 					rejected,
+					span,
 				);
 				self.values.push(expr);
 				if let Some(rejected) = maybe_rejected {
-					return Err(ParseError::UnexpectedToken(rejected));
+					self.recover_from(ParseError::UnexpectedToken(rejected.clone(), span), errors);
+					return self.add_token(rejected, span, errors);
 				}
 			},
 		}
 		Ok(None)
 	}
 
-	fn finish(self: Box<Self>) -> Result<Box<dyn Expression>, ParseError> {
-		let mut values = Vec::new();
-		for expr_builder in self.values.into_iter() {
-			values.push(expr_builder.finish()?)
+	fn finish(
+		self: Box<Self>,
+		end: Span,
+		errors: &mut Vec<ParseError>,
+	) -> Result<Expression, ParseError> {
+		// a single parenthesized expression without a comma is just grouping, e.g. `(a + b)`
+		let is_grouping = self.values.len() == 1 && !self.seen_comma;
+		let mut values = self.finish_values(end, errors);
+		if is_grouping {
+			return Ok(values
+				.pop()
+				.expect("A grouping should've had exactly one value"));
 		}
-		if !self.closed {
-			return Err(ParseError::ExpectedTokens);
+		Ok(Expression::Tuple(TupleExpr { values }))
+	}
+}
+
+/// Represents a function call being built, e.g.
+/// `name(`
+/// `name(a, b`
+/// `name(a, b)`
+struct CallExprBuilder {
+	name: String,
+	args: TupleExprBuilder,
+}
+
+impl CallExprBuilder {
+	fn new(name: String) -> Self {
+		Self {
+			name,
+			args: TupleExprBuilder::new(),
 		}
-		Ok(Box::new(TupleExpr { values }))
+	}
+}
+
+impl ExpressionBuilder for CallExprBuilder {
+	fn add_token(
+		&mut self,
+		token: Token,
+		span: Span,
+		errors: &mut Vec<ParseError>,
+	) -> Result<Option<Token>, ParseError> {
+		// the call is closed as soon as its arguments are
+		self.args.add_token(token, span, errors)
+	}
+
+	fn finish(
+		self: Box<Self>,
+		end: Span,
+		errors: &mut Vec<ParseError>,
+	) -> Result<Expression, ParseError> {
+		Ok(Expression::Call(CallExpr {
+			name: self.name,
+			args: self.args.finish_values(end, errors),
+		}))
 	}
 }
 
@@ -305,23 +701,33 @@ struct SpecialSyntaxExprBuilder {
 	seen_curlies: (bool, bool),
 	// .0 is if the left one was seen, .1 is if the right one was seen
 	// (false, true) is not a valid state
-	inner_expr: Box<dyn ExpressionBuilder>,
+	// an unknown syntax is reported as soon as the block is given anything
+	inner_expr: Result<Box<dyn ExpressionBuilder>, ParseError>,
 }
 
 impl SpecialSyntaxExprBuilder {
-	fn new(syntax_name: &str) -> Self {
+	/// `span` is the span of the `!`
+	fn new(syntax_name: &str, span: Span) -> Self {
 		Self {
 			seen_curlies: (false, false),
-			inner_expr: lookup_special_syntax_builder(syntax_name),
+			inner_expr: special_syntax::lookup_special_syntax(syntax_name)
+				.map(|syntax| syntax())
+				.ok_or_else(|| ParseError::UnknownSpecialSyntax(syntax_name.to_string(), span)),
 		}
 	}
 }
 
 impl ExpressionBuilder for SpecialSyntaxExprBuilder {
-	fn add_token(&mut self, token: Token) -> Result<Option<Token>, ParseError> {
+	fn add_token(
+		&mut self,
+		token: Token,
+		span: Span,
+		errors: &mut Vec<ParseError>,
+	) -> Result<Option<Token>, ParseError> {
 		if self.seen_curlies.1 {
 			return Ok(Some(token));
 		}
+		let inner_expr = self.inner_expr.as_mut().map_err(|error| error.clone())?;
 
 		if !self.seen_curlies.0 {
 			match token {
@@ -330,32 +736,88 @@ impl ExpressionBuilder for SpecialSyntaxExprBuilder {
 					return Ok(None);
 				},
 				Token::Whitespace => return Ok(None),
-				_ => return Err(ParseError::UnexpectedToken(token)),
+				_ => return Err(ParseError::UnexpectedToken(token, span)),
 			}
 		}
 
-		match self.inner_expr.add_token(token)? {
-			None => (),
-			Some(Token::Whitespace) => (),
-			Some(Token::RCurly) => self.seen_curlies.1 = true,
-			Some(rejected) => {
-				// don't attempt to create an infix expression
-				return Err(ParseError::UnexpectedToken(rejected));
+		let error = match inner_expr.add_token(token.clone(), span, errors) {
+			Ok(None) | Ok(Some(Token::Whitespace)) => return Ok(None),
+			Ok(Some(Token::RCurly)) => {
+				self.seen_curlies.1 = true;
+				return Ok(None);
 			},
-		}
-		Ok(None)
+			// don't attempt to create an infix expression
+			Ok(Some(rejected)) => ParseError::UnexpectedToken(rejected, span),
+			Err(error) => error,
+		};
+		// skip ahead to the closing `}`, starting with the token that broke the inner expression
+		errors.push(error.clone());
+		self.inner_expr = Ok(Box::new(ErrorExprBuilder::new(error, &[Token::RCurly])));
+		self.add_token(token, span, errors)
 	}
 
-	fn finish(self: Box<Self>) -> Result<Box<dyn Expression>, ParseError> {
+	fn finish(
+		self: Box<Self>,
+		end: Span,
+		errors: &mut Vec<ParseError>,
+	) -> Result<Expression, ParseError> {
+		let inner_expr = self.inner_expr?;
 		if self.seen_curlies != (true, true) {
-			return Err(ParseError::ExpectedTokens);
+			return Err(ParseError::ExpectedTokens(end));
+		}
+		inner_expr.finish(end, errors).or_else(|error| {
+			errors.push(error.clone());
+			Ok(Expression::Error(ErrorExpr { error }))
+		})
+	}
+}
+
+/// Stands in for a malformed part of the source, swallowing tokens until one of the closers appears
+/// outside of any brackets opened since the error
+struct ErrorExprBuilder {
+	error: ParseError,
+	closers: &'static [Token],
+	depth: usize,
+}
+
+impl ErrorExprBuilder {
+	fn new(error: ParseError, closers: &'static [Token]) -> Self {
+		Self {
+			error,
+			closers,
+			depth: 0,
 		}
-		Ok(self.inner_expr.finish()?)
 	}
 }
 
-fn lookup_special_syntax_builder(_syntax_name: &str) -> Box<dyn ExpressionBuilder> {
-	todo!();
+impl ExpressionBuilder for ErrorExprBuilder {
+	fn add_token(
+		&mut self,
+		token: Token,
+		_span: Span,
+		_errors: &mut Vec<ParseError>,
+	) -> Result<Option<Token>, ParseError> {
+		if self.depth == 0 && self.closers.contains(&token) {
+			return Ok(Some(token));
+		}
+		match token {
+			Token::LParen | Token::LBrack | Token::LCurly => self.depth += 1,
+			Token::RParen | Token::RBrack | Token::RCurly => {
+				self.depth = self.depth.saturating_sub(1)
+			},
+			_ => (),
+		}
+		Ok(None)
+	}
+
+	fn finish(
+		self: Box<Self>,
+		_end: Span,
+		_errors: &mut Vec<ParseError>,
+	) -> Result<Expression, ParseError> {
+		// the error was recorded when it was found
+		Ok(Expression::Error(ErrorExpr { error: self.error }))
+	}
 }
 
 struct InfixOperationsExprBuilder {
@@ -375,11 +837,16 @@ impl InfixOperationsExprBuilder {
 	/// token to create a new expression that will become the current active expression. If the
 	/// current active expression rejects the token, this method will return that; if the token is
 	/// used to create an expression but this fails, this method propagates the error.
-	fn add_to_current_active_expr(&mut self, token: Token) -> Result<Option<Token>, ParseError> {
+	fn add_to_current_active_expr(
+		&mut self,
+		token: Token,
+		span: Span,
+		errors: &mut Vec<ParseError>,
+	) -> Result<Option<Token>, ParseError> {
 		if self.operands.len() == self.operators.len() {
 			// the last item added was an operator
 			if !matches!(token, Token::Whitespace) {
-				self.operands.push(create_new_expression(token)?);
+				self.operands.push(create_new_expression(token, span)?);
 			}
 			Ok(None)
 		} else if self.operands.len() == self.operators.len() + 1 {
@@ -387,66 +854,369 @@ impl InfixOperationsExprBuilder {
 			self.operands
 				.last_mut()
 				.expect("There should've been an active operand")
-				.add_token(token)
+				.add_token(token, span, errors)
 		} else {
 			unreachable!()
 		}
 	}
+
+	/// Records the error and replaces the active operand with a placeholder that skips ahead to the
+	/// next operator, or to whatever ends the operation
+	fn recover_from(&mut self, error: ParseError, errors: &mut Vec<ParseError>) {
+		errors.push(error.clone());
+		if self.operands.len() > self.operators.len() {
+			self.operands.pop();
+		}
+		self.operands.push(Box::new(ErrorExprBuilder::new(
+			error,
+			&[
+				Token::PlusSign,
+				Token::MinusSign,
+				Token::MulSign,
+				Token::DivSign,
+				Token::PowSign,
+				Token::Comma,
+				Token::RParen,
+				Token::RCurly,
+				Token::Arrow,
+				Token::ReversibleArrow,
+			],
+		)));
+	}
 }
 
 impl ExpressionBuilder for InfixOperationsExprBuilder {
-	fn add_token(&mut self, token: Token) -> Result<Option<Token>, ParseError> {
-		match self.add_to_current_active_expr(token)? {
+	fn add_token(
+		&mut self,
+		token: Token,
+		span: Span,
+		errors: &mut Vec<ParseError>,
+	) -> Result<Option<Token>, ParseError> {
+		let rejected = match self.add_to_current_active_expr(token.clone(), span, errors) {
+			Ok(rejected) => rejected,
+			Err(error) => {
+				// skip the rest of the malformed operand, starting with the token that broke it; an
+				// operator that broke it is part of it, e.g. the second `*` of `2 * * 3`
+				self.recover_from(error, errors);
+				if InfixOperator::try_from(token.clone()).is_ok() {
+					return Ok(None);
+				}
+				return self.add_token(token, span, errors);
+			},
+		};
+		match rejected {
 			None => (),
 			Some(Token::Whitespace) => (),
 			Some(rejected) => match InfixOperator::try_from(rejected) {
 				Ok(operator) => self.operators.push(operator),
-				Err(bad_token) => {
-					// Since we must be in a valid state, reject the bad token and close this
-					// InfixOperationsExprBuilder
+				Err(not_operator) => {
+					// the token may still apply to the last operand alone, e.g. `a + 2$O2`
 					assert_eq!(self.operands.len() - self.operators.len(), 1);
-					return Ok(Some(bad_token));
+					let (operand, maybe_rejected) = wrap_in_postfix(
+						self.operands
+							.pop()
+							.expect("There should've been an active operand"),
+						not_operator,
+						span,
+					);
+					self.operands.push(operand);
+					if let Some(bad_token) = maybe_rejected {
+						// Since we must be in a valid state, reject the bad token and close this
+						// InfixOperationsExprBuilder
+						return Ok(Some(bad_token));
+					}
 				},
 			},
 		}
 		Ok(None)
 	}
 
-	fn finish(self: Box<Self>) -> Result<Box<dyn Expression>, ParseError> {
+	fn finish(
+		self: Box<Self>,
+		end: Span,
+		errors: &mut Vec<ParseError>,
+	) -> Result<Expression, ParseError> {
 		// assert_eq!(self.operands.len() - self.operators.len(), 1); make this an Err() instead of panic
 		if self.operands.len() - self.operators.len() != 1 {
-			return Err(ParseError::ExpectedTokens);
+			return Err(ParseError::ExpectedTokens(end));
 		}
 		let mut operands = Vec::new();
 		for expr_builder in self.operands {
-			operands.push(expr_builder.finish()?);
+			operands.push(expr_builder.finish(end, errors)?);
 		}
-		Ok(Box::new(InfixOperationsExpr {
-			operands,
-			operators: self.operators,
-		}))
+		let mut operands = operands.into_iter();
+		let first_operand = operands
+			.next()
+			.expect("There should've been at least one operand");
+		let mut operators = self.operators.into_iter().peekable();
+		Ok(combine_with_precedence(
+			first_operand,
+			0,
+			&mut operands,
+			&mut operators,
+		))
 	}
+
+	fn into_equation_side(
+		self: Box<Self>,
+		end: Span,
+		errors: &mut Vec<ParseError>,
+	) -> Result<EquationSide, ParseError> {
+		if !self
+			.operators
+			.iter()
+			.all(|operator| matches!(operator, InfixOperator::Plus))
+		{
+			return Ok(vec![(1, self.finish(end, errors)?)]);
+		}
+		if self.operands.len() - self.operators.len() != 1 {
+			return Err(ParseError::ExpectedTokens(end));
+		}
+		let mut terms = Vec::new();
+		for operand in self.operands {
+			terms.append(&mut operand.into_equation_side(end, errors)?);
+		}
+		Ok(terms)
+	}
+}
+
+/// Combines the operands into a tree of infix operations by precedence climbing: starting with the
+/// specified left-hand side, consumes operators of at least the specified precedence (along with
+/// their right-hand operands) and returns the combined expression.
+fn combine_with_precedence(
+	mut lhs: Expression,
+	min_precedence: u8,
+	operands: &mut impl Iterator<Item = Expression>,
+	operators: &mut Peekable<impl Iterator<Item = InfixOperator>>,
+) -> Expression {
+	while let Some(operator) = operators.next_if(|operator| operator.precedence() >= min_precedence)
+	{
+		let mut rhs = operands
+			.next()
+			.expect("There should be one more operand than operators");
+		// anything binding more tightly than the current operator applies to the rhs first
+		while let Some(&next_operator) = operators.peek() {
+			if next_operator.precedence() > operator.precedence()
+				|| (next_operator.is_right_associative()
+					&& next_operator.precedence() == operator.precedence())
+			{
+				rhs = combine_with_precedence(rhs, next_operator.precedence(), operands, operators);
+			} else {
+				break;
+			}
+		}
+		lhs = Expression::Infix(InfixOperationExpr {
+			lhs: Box::new(lhs),
+			operator,
+			rhs: Box::new(rhs),
+		});
+	}
+	lhs
 }
 
+/// Attempts to use the token to combine the (closed) expression into a larger expression, e.g. as
+/// the first operand of an infix operation or the reactants of a chemical equation. If this isn't
+/// possible, returns the expression unchanged along with the token.
 fn wrap_in_infix(
 	expr: Box<dyn ExpressionBuilder>,
 	maybe_operator: Token,
+	span: Span,
 ) -> (Box<dyn ExpressionBuilder>, Option<Token>) {
 	match InfixOperator::try_from(maybe_operator) {
 		Ok(operator) => (
 			Box::new(InfixOperationsExprBuilder::new(expr, operator)),
 			None,
 		),
-		Err(bang @ Token::Bang) => (
+		Err(arrow @ (Token::Arrow | Token::ReversibleArrow)) => (
+			Box::new(ChemEqnExprBuilder::new(
+				expr,
+				matches!(arrow, Token::ReversibleArrow),
+			)),
+			None,
+		),
+		Err(not_operator) => wrap_in_postfix(expr, not_operator, span),
+	}
+}
+
+/// Like wrap_in_infix, but only for tokens that bind to the single expression before them, so that
+/// it can also be applied to the last operand of an infix operation.
+fn wrap_in_postfix(
+	expr: Box<dyn ExpressionBuilder>,
+	token: Token,
+	span: Span,
+) -> (Box<dyn ExpressionBuilder>, Option<Token>) {
+	match token {
+		bang @ Token::Bang => (
 			Box::new(SpecialSyntaxExprBuilder::new(
 				match expr.parsing_time_identifier() {
-					Ok(syntax_name) => syntax_name,
-					Err(_) => return (expr, Some(bang)),
+					Some(syntax_name) => syntax_name,
+					None => return (expr, Some(bang)),
 				},
+				span,
 			)),
 			None,
 		),
-		Err(rejected) => (expr, Some(rejected)),
+		Token::LParen => match expr.parsing_time_identifier() {
+			Some(name) => (Box::new(CallExprBuilder::new(name.to_string())), None),
+			None => (expr, Some(Token::LParen)),
+		},
+		cash @ (Token::Cash | Token::CashCash) => match expr.parsing_time_integer() {
+			Some(coefficient) => (
+				Box::new(CoefficientExprBuilder::new(
+					coefficient,
+					create_new_expression(cash, span).expect("A formula can always be started"),
+				)),
+				None,
+			),
+			None => (expr, Some(cash)),
+		},
+		rejected => (expr, Some(rejected)),
+	}
+}
+
+/// Represents a species being built with a leading coefficient, e.g.
+/// `2$`
+/// `2$H2O`
+struct CoefficientExprBuilder {
+	coefficient: u32,
+	species: Box<dyn ExpressionBuilder>,
+}
+
+impl CoefficientExprBuilder {
+	fn new(coefficient: u32, species: Box<dyn ExpressionBuilder>) -> Self {
+		Self {
+			coefficient,
+			species,
+		}
+	}
+}
+
+impl ExpressionBuilder for CoefficientExprBuilder {
+	fn add_token(
+		&mut self,
+		token: Token,
+		span: Span,
+		errors: &mut Vec<ParseError>,
+	) -> Result<Option<Token>, ParseError> {
+		// the coefficient is closed as soon as the species is
+		self.species.add_token(token, span, errors)
+	}
+
+	fn finish(
+		self: Box<Self>,
+		end: Span,
+		errors: &mut Vec<ParseError>,
+	) -> Result<Expression, ParseError> {
+		let coefficient = self.coefficient;
+		let with_coefficient = |species| {
+			Expression::Coefficient(CoefficientExpr {
+				coefficient,
+				species: Box::new(species),
+			})
+		};
+		Ok(match self.species.finish(end, errors)? {
+			// a method called on a condensed formula applies to the whole species, as it does for
+			// other formulas, e.g. `2$$H2O.mass` is the mass of two waters
+			Expression::Infix(InfixOperationExpr {
+				lhs,
+				operator: InfixOperator::FunctionCall,
+				rhs,
+			}) => Expression::Infix(InfixOperationExpr {
+				lhs: Box::new(with_coefficient(*lhs)),
+				operator: InfixOperator::FunctionCall,
+				rhs,
+			}),
+			species => with_coefficient(species),
+		})
+	}
+
+	fn into_equation_side(
+		self: Box<Self>,
+		end: Span,
+		errors: &mut Vec<ParseError>,
+	) -> Result<EquationSide, ParseError> {
+		Ok(vec![(self.coefficient, self.species.finish(end, errors)?)])
+	}
+}
+
+/// Represents a chemical equation being built, e.g.
+/// `$CH4 + 2$O2 ->`
+/// `$CH4 + 2$O2 -> $CO2 + 2$H2O`
+/// The reactants are already closed by the time the arrow is encountered.
+struct ChemEqnExprBuilder {
+	reactants: Box<dyn ExpressionBuilder>,
+	reversible: bool,
+	maybe_products: Option<Box<dyn ExpressionBuilder>>,
+	closed: bool,
+}
+
+impl ChemEqnExprBuilder {
+	fn new(reactants: Box<dyn ExpressionBuilder>, reversible: bool) -> Self {
+		Self {
+			reactants,
+			reversible,
+			maybe_products: None,
+			closed: false,
+		}
+	}
+}
+
+impl ExpressionBuilder for ChemEqnExprBuilder {
+	fn add_token(
+		&mut self,
+		token: Token,
+		span: Span,
+		errors: &mut Vec<ParseError>,
+	) -> Result<Option<Token>, ParseError> {
+		if self.closed {
+			return Ok(Some(token));
+		}
+
+		match &mut self.maybe_products {
+			None => {
+				if !matches!(token, Token::Whitespace) {
+					self.maybe_products = Some(create_new_expression(token, span)?);
+				}
+			},
+			Some(products) => match products.add_token(token, span, errors)? {
+				None => (),
+				Some(Token::Whitespace) => (),
+				Some(arrow @ (Token::Arrow | Token::ReversibleArrow)) => {
+					// an equation can only have one arrow
+					return Err(ParseError::UnexpectedToken(arrow, span));
+				},
+				Some(rejected) => {
+					let (expr, maybe_rejected) = wrap_in_infix(
+						self.maybe_products
+							.take()
+							.expect("There should've been a products expr"),
+						rejected,
+						span,
+					);
+					self.maybe_products = Some(expr);
+					if let Some(bad_token) = maybe_rejected {
+						self.closed = true;
+						return Ok(Some(bad_token));
+					}
+				},
+			},
+		}
+		Ok(None)
+	}
+
+	fn finish(
+		self: Box<Self>,
+		end: Span,
+		errors: &mut Vec<ParseError>,
+	) -> Result<Expression, ParseError> {
+		Ok(Expression::ChemEqn(ChemEqnExpr {
+			reactants: self.reactants.into_equation_side(end, errors)?,
+			products: self
+				.maybe_products
+				.ok_or(ParseError::ExpectedTokens(end))?
+				.into_equation_side(end, errors)?,
+			reversible: self.reversible,
+		}))
 	}
 }
 
@@ -488,12 +1258,17 @@ impl MolecularFormulaExprBuilder {
 	/// Attempts to add the specified token to the last symbol or subscript expression; if this
 	/// gets rejected, then attempts to create a next symbol/subscript expression; if this gets
 	/// rejected, returns the token
-	fn add_to_symbol_or_subscr(&mut self, mut token: Token) -> Result<Option<Token>, ParseError> {
+	fn add_to_symbol_or_subscr(
+		&mut self,
+		mut token: Token,
+		span: Span,
+		errors: &mut Vec<ParseError>,
+	) -> Result<Option<Token>, ParseError> {
 		assert!(self.charge.is_none());
 
 		// Attempt to add it to the last symbol/subscr
 		if let Some(active_expr) = self.symbols_and_subscripts.last_mut() {
-			match active_expr.add_token(token)? {
+			match active_expr.add_token(token, span, errors)? {
 				None => return Ok(None),
 				Some(rejected) => token = rejected, // put the token back into the variable
 			}
@@ -502,12 +1277,12 @@ impl MolecularFormulaExprBuilder {
 		// Either there were no subexpressions or the last subexpression rejected
 		// So try to create another expression using the token
 		if !matches!(token, Token::Whitespace) {
-			match create_new_expression(token) {
+			match create_new_expression(token, span) {
 				Ok(expr) => {
 					self.symbols_and_subscripts.push(expr);
 					return Ok(None);
 				},
-				Err(ParseError::UnexpectedToken(rejected)) => token = rejected,
+				Err(ParseError::UnexpectedToken(rejected, _)) => token = rejected,
 				_ => unreachable!("create_new_expression should never return anything else"),
 			}
 		}
@@ -518,7 +1293,12 @@ impl MolecularFormulaExprBuilder {
 }
 
 impl ExpressionBuilder for MolecularFormulaExprBuilder {
-	fn add_token(&mut self, token: Token) -> Result<Option<Token>, ParseError> {
+	fn add_token(
+		&mut self,
+		token: Token,
+		span: Span,
+		errors: &mut Vec<ParseError>,
+	) -> Result<Option<Token>, ParseError> {
 		if self.is_closed {
 			return Ok(Some(token));
 		}
@@ -526,7 +1306,7 @@ impl ExpressionBuilder for MolecularFormulaExprBuilder {
 		match &mut self.charge {
 			None => {
 				// no sign encountered yet
-				if let Some(rejected) = self.add_to_symbol_or_subscr(token)? {
+				if let Some(rejected) = self.add_to_symbol_or_subscr(token, span, errors)? {
 					match rejected {
 						Token::PlusSign => self.charge = Some((true, None)),
 						Token::MinusSign => self.charge = Some((false, None)),
@@ -542,16 +1322,20 @@ impl ExpressionBuilder for MolecularFormulaExprBuilder {
 			},
 			Some((_, magn_option @ None)) => {
 				// a sign has been encountered but no magnitude expression
-				*magn_option = Some(create_new_expression(token)?);
-				// cannot be whitespace; once a sign is encountered, a magnitude expression is
-				// immediately expected
-				Ok(None)
+				if matches!(token, Token::Integer(_)) {
+					*magn_option = Some(create_new_expression(token, span)?);
+					Ok(None)
+				} else {
+					// a sign without a magnitude is a charge of one, e.g. `$NH4+`
+					self.is_closed = true;
+					Ok(Some(token))
+				}
 			},
 			Some((_, Some(magn))) => {
 				// a magnitude expression exists
 				// if the magntiude expression accepts, the token, so does the whole formula; if the
 				// magnitude expression rejects, so does the whole formula
-				match magn.add_token(token)? {
+				match magn.add_token(token, span, errors)? {
 					Some(rejected) => {
 						self.is_closed = true;
 						Ok(Some(rejected))
@@ -562,50 +1346,516 @@ impl ExpressionBuilder for MolecularFormulaExprBuilder {
 		}
 	}
 
-	fn finish(self: Box<Self>) -> Result<Box<dyn Expression>, ParseError> {
-		// account the possibility for something like "$CO3+"
+	fn finish(
+		self: Box<Self>,
+		end: Span,
+		errors: &mut Vec<ParseError>,
+	) -> Result<Expression, ParseError> {
 		let mut symbols_and_subscripts = Vec::new();
 		for symbol_or_subscr in self.symbols_and_subscripts.into_iter() {
-			symbols_and_subscripts.push(symbol_or_subscr.finish()?)
+			symbols_and_subscripts.push(symbol_or_subscr.finish(end, errors)?)
 		}
-		Ok(Box::new(MolecularFormulaExpr {
+		Ok(Expression::MolecularFormula(MolecularFormulaExpr {
 			symbols_and_subscripts,
-			_charge: match self.charge {
-				None => None,
-				Some((sign, maybe_magn)) => Some((
-					sign,
-					maybe_magn.ok_or(ParseError::ExpectedTokens)?.finish()?,
-				)),
-			},
+			charge: finish_charge(self.charge, end, errors)?,
 		}))
 	}
 }
 
+fn finish_charge(
+	charge: Option<(bool, Option<Box<dyn ExpressionBuilder>>)>,
+	end: Span,
+	errors: &mut Vec<ParseError>,
+) -> Result<Option<ChargeExpr>, ParseError> {
+	let Some((is_positive, magnitude)) = charge else {
+		return Ok(None);
+	};
+	let magnitude = match magnitude {
+		None => None,
+		Some(magn) => Some(Box::new(magn.finish(end, errors)?)),
+	};
+	Ok(Some(ChargeExpr {
+		is_positive,
+		magnitude,
+	}))
+}
+
+/// Represents a condensed formula being built, e.g.
+/// `$$Ca(OH`
+/// `$$(NH4)3PO4`
+/// `$$CuSO4.5H2O`
+/// `$$Fe(CN)6-3`
+/// `$$Ca(OH)2.mass`
 struct CondensedFormulaExprBuilder {
-	subformulas_and_subscripts: Vec<Box<dyn ExpressionBuilder>>,
+	// the formula itself followed by each adduct, along with their coefficients; the last one is
+	// the one being built
+	units: Vec<(u32, Vec<CondensedPartExpr>)>,
+	// the parts of each parenthesized group that hasn't been closed yet, innermost last
+	open_groups: Vec<Vec<CondensedPartExpr>>,
+	// an isotope being built, e.g. `[13]C`, which becomes a part once it is finished
+	active_isotope: Option<Box<dyn ExpressionBuilder>>,
+	// a `.` that starts either an adduct, e.g. `$$CuCl.2H2O`, or a method call on the formula, e.g.
+	// `$$Ca(OH)2.mass`, depending on the token after it
+	pending_dot: Option<Span>,
+	// the method called on the formula, which is the last thing in it
+	method: Option<Box<dyn ExpressionBuilder>>,
 	charge: Option<(bool, Option<Box<dyn ExpressionBuilder>>)>,
 	// true is positive, false is negative; None value means that part of the syntax hasn't been
 	// encountered yet
+	is_closed: bool,
 }
 
 impl CondensedFormulaExprBuilder {
 	fn new() -> Self {
 		Self {
-			subformulas_and_subscripts: Vec::new(),
+			units: vec![(1, Vec::new())],
+			open_groups: Vec::new(),
+			active_isotope: None,
 			charge: None,
+			pending_dot: None,
+			method: None,
+			is_closed: false,
+		}
+	}
+
+	/// Returns the parts of the innermost group or adduct being built
+	fn active_parts(&mut self) -> &mut Vec<CondensedPartExpr> {
+		match self.open_groups.last_mut() {
+			Some(parts) => parts,
+			None => {
+				&mut self
+					.units
+					.last_mut()
+					.expect("There should've been a unit")
+					.1
+			},
 		}
 	}
+
+	/// Whether the formula could end here, i.e. all groups are closed and nothing is left empty
+	fn is_complete(&self) -> bool {
+		self.open_groups.is_empty()
+			&& !self
+				.units
+				.last()
+				.expect("There should've been a unit")
+				.1
+				.is_empty()
+	}
+
+	/// Sets the subscript of the last part, or if an adduct was just started, its coefficient
+	fn set_subscript(
+		&mut self,
+		subscript: u32,
+		token: Token,
+		span: Span,
+	) -> Result<(), ParseError> {
+		let at_adduct_start = self.open_groups.is_empty() && self.units.len() > 1;
+		match self.active_parts().last_mut() {
+			Some(CondensedPartExpr::Element(_, old) | CondensedPartExpr::Group(_, old)) => {
+				*old = subscript
+			},
+			None if at_adduct_start => {
+				self.units
+					.last_mut()
+					.expect("There should've been a unit")
+					.0 = subscript
+			},
+			_ => return Err(ParseError::UnexpectedToken(token, span)),
+		}
+		Ok(())
+	}
+
+	fn start_adduct(
+		&mut self,
+		coefficient: u32,
+		token: Token,
+		span: Span,
+	) -> Result<(), ParseError> {
+		if !self.is_complete() {
+			return Err(ParseError::UnexpectedToken(token, span));
+		}
+		self.units.push((coefficient, Vec::new()));
+		Ok(())
+	}
+
+	/// Makes the isotope being built (if any) into a part
+	fn finish_isotope(
+		&mut self,
+		end: Span,
+		errors: &mut Vec<ParseError>,
+	) -> Result<(), ParseError> {
+		if let Some(isotope) = self.active_isotope.take() {
+			let element = isotope.finish(end, errors)?;
+			self.active_parts()
+				.push(CondensedPartExpr::Element(element, 1));
+		}
+		Ok(())
+	}
+
+	fn add_to_parts(
+		&mut self,
+		mut token: Token,
+		span: Span,
+		errors: &mut Vec<ParseError>,
+	) -> Result<Option<Token>, ParseError> {
+		if let Some(isotope) = &mut self.active_isotope {
+			match isotope.add_token(token, span, errors)? {
+				None => return Ok(None),
+				Some(rejected) => token = rejected,
+			}
+			self.finish_isotope(span, errors)?;
+		}
+
+		if let Some(dot_span) = self.pending_dot.take() {
+			match token {
+				// element symbols are capitalized, so anything else names a method
+				Token::Identifier(ref name)
+					if !name.starts_with(|c: char| c.is_ascii_uppercase()) =>
+				{
+					self.method = Some(create_new_expression(token, span)?);
+					return Ok(None);
+				},
+				_ => self.start_adduct(1, Token::Dot, dot_span)?,
+			}
+		}
+
+		match token {
+			Token::LBrack => self.active_isotope = Some(Box::new(IsotopeExprBuilder::new())),
+			Token::Identifier(ref symbol) => {
+				let element = Expression::Identifier(Identifier {
+					name: symbol.clone(),
+				});
+				self.active_parts()
+					.push(CondensedPartExpr::Element(element, 1));
+			},
+			Token::Integer(ref digits) => {
+				let subscript = parse_subscript(digits, &token, span)?;
+				self.set_subscript(subscript, token, span)?;
+			},
+			Token::Dot if self.is_complete() => self.pending_dot = Some(span),
+			Token::Unknown(ref string) if string == "·" => self.start_adduct(1, token, span)?,
+			Token::LParen => self.open_groups.push(Vec::new()),
+			Token::RParen if !self.open_groups.is_empty() => {
+				let parts = self
+					.open_groups
+					.pop()
+					.expect("Should've checked for an open group");
+				if parts.is_empty() {
+					return Err(ParseError::UnexpectedToken(token, span));
+				}
+				self.active_parts().push(CondensedPartExpr::Group(parts, 1));
+			},
+			Token::PlusSign if self.is_complete() => self.charge = Some((true, None)),
+			Token::MinusSign if self.is_complete() => self.charge = Some((false, None)),
+			token if self.is_complete() => {
+				self.is_closed = true;
+				return Ok(Some(token));
+			},
+			token => return Err(ParseError::UnexpectedToken(token, span)),
+		}
+		Ok(None)
+	}
+}
+
+fn parse_subscript(digits: &str, token: &Token, span: Span) -> Result<u32, ParseError> {
+	if !digits.chars().all(|c| c.is_ascii_digit()) {
+		return Err(ParseError::UnexpectedToken(token.clone(), span));
+	}
+	digits
+		.parse()
+		.map_err(|_| ParseError::UnexpectedToken(token.clone(), span))
 }
 
 impl ExpressionBuilder for CondensedFormulaExprBuilder {
-	fn add_token(&mut self, token: Token) -> Result<Option<Token>, ParseError> {
-		todo!();
+	fn add_token(
+		&mut self,
+		token: Token,
+		span: Span,
+		errors: &mut Vec<ParseError>,
+	) -> Result<Option<Token>, ParseError> {
+		if self.is_closed {
+			return Ok(Some(token));
+		}
+		if let Some(mut method) = self.method.take() {
+			let (method, rejected) = match method.add_token(token, span, errors)? {
+				None => (method, None),
+				// the method may take arguments, e.g. `$$Ca(OH)2.count(O)`
+				Some(rejected) => wrap_in_postfix(method, rejected, span),
+			};
+			self.method = Some(method);
+			self.is_closed = rejected.is_some();
+			return Ok(rejected);
+		}
+
+		match &mut self.charge {
+			None => self.add_to_parts(token, span, errors),
+			Some((_, magn_option @ None)) if matches!(token, Token::Integer(_)) => {
+				*magn_option = Some(create_new_expression(token, span)?);
+				Ok(None)
+			},
+			Some(_) => {
+				// the charge is complete, e.g. `$$(NH4)+` or `$$Fe(CN)6-3`
+				self.is_closed = true;
+				Ok(Some(token))
+			},
+		}
 	}
 
-	fn finish(self: Box<Self>) -> Result<Box<dyn Expression>, ParseError> {
-		todo!();
+	fn finish(
+		mut self: Box<Self>,
+		end: Span,
+		errors: &mut Vec<ParseError>,
+	) -> Result<Expression, ParseError> {
+		self.finish_isotope(end, errors)?;
+		if !self.is_complete() || self.pending_dot.is_some() {
+			return Err(ParseError::ExpectedTokens(end));
+		}
+		let mut units = self.units.into_iter();
+		let (_, mut parts) = units.next().expect("There should've been a unit");
+		for (coefficient, adduct_parts) in units {
+			parts.push(CondensedPartExpr::Adduct(coefficient, adduct_parts));
+		}
+		let formula = Expression::CondensedFormula(CondensedFormulaExpr {
+			parts,
+			charge: finish_charge(self.charge, end, errors)?,
+		});
+		Ok(match self.method {
+			None => formula,
+			Some(method) => Expression::Infix(InfixOperationExpr {
+				lhs: Box::new(formula),
+				operator: InfixOperator::FunctionCall,
+				rhs: Box::new(method.finish(end, errors)?),
+			}),
+		})
 	}
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+	use super::*;
+	use crate::chem_data::{
+		chem_eqn::{AddSpeciesError, ChemEqn, Num},
+		elements::PeriodicTable,
+		formulas::tests::formula,
+	};
+
+	#[test]
+	fn parses_equations() {
+		let p_table = PeriodicTable::new_alphabetic();
+		let mut dict = Dictionary::new();
+		dict.load_elements(&p_table);
+
+		let mut should_be = ChemEqn::new();
+		let specieses: [(&[(&str, u32)], Num); 4] = [
+			(&[("Ch", 1), ("He", 4)], -1),
+			(&[("Os", 2)], -2),
+			(&[("Ch", 1), ("Os", 2)], 1),
+			(&[("He", 2), ("Os", 1)], 2),
+		];
+		for (parts, coeff) in specieses {
+			should_be
+				.add_species(formula(&p_table, parts), coeff)
+				.unwrap();
+		}
+
+		let parsed = parse_str("$ChHe4 + 2$Os2 -> $ChOs2 + 2 $He2Os").unwrap();
+		assert_eq!(
+			parsed.evaluate(&dict).unwrap(),
+			Value::ChemEqn(should_be.clone())
+		);
+
+		should_be.set_reversible(true);
+		// without spaces, `+2` would be a charge
+		assert!(parse_str("$ChHe4+2$Os2<=>$ChOs2+2$He2Os").is_err());
+		let parsed = parse_str("$ChHe4 + 2$Os2 + $Al2 <-> $ChOs2 + 2$He2Os + $Al2").unwrap();
+		assert!(matches!(
+			parsed.evaluate(&dict),
+			Err(EvaluationError::Species(AddSpeciesError::OnBothSides { species }))
+				if species == "Al2"
+		));
+		let parsed = parse_str("$ChHe4 + $Os2 + $Os2 <-> $ChOs2 + 2$He2Os").unwrap();
+		assert_eq!(parsed.evaluate(&dict).unwrap(), Value::ChemEqn(should_be));
+
+		// a species needs atoms or a charge, and its coefficients have to fit
+		for (string, error) in [
+			("$Os2 * 0 -> $Os2", AddSpeciesError::Empty),
+			("$Os2 -> ($Os2 - $Os2)", AddSpeciesError::Empty),
+			("2147483647$Os2 + 2$Os2 -> $Ch", AddSpeciesError::Overflow),
+		] {
+			match parse_str(string).unwrap().evaluate(&dict) {
+				Err(EvaluationError::Species(found)) => assert_eq!(found, error, "{}", string),
+				other => panic!("{} should've failed, not given {:?}", string, other),
+			}
+		}
+	}
+
+	#[test]
+	fn evaluates_real_literals() {
+		let dict = Dictionary::new();
+		assert_eq!(
+			parse_str("6.02e-23").unwrap().evaluate(&dict).unwrap(),
+			Value::RealNumber(RealNumber::new(6.02e-23, 3))
+		);
+		assert_eq!(
+			parse_str("1.50").unwrap().evaluate(&dict).unwrap(),
+			Value::RealNumber(RealNumber::new(1.5, 3))
+		);
+		assert_eq!(
+			parse_str("1.e5").unwrap().evaluate(&dict).unwrap(),
+			Value::RealNumber(RealNumber::new(1e5, 1))
+		);
+		for literal in ["1.5e", "1.0e400", "1.0e-400"] {
+			assert!(
+				matches!(
+					parse_str(literal).unwrap().evaluate(&dict),
+					Err(EvaluationError::MalformedReal(_))
+				),
+				"{}",
+				literal
+			);
+		}
+	}
+
+	#[test]
+	fn rejects_malformed_equations() {
+		assert_eq!(
+			parse_str("$Al -> $Bo -> $Ch").unwrap_err(),
+			ParseError::UnexpectedToken(Token::Arrow, Span::new(11, 13))
+		);
+		assert_eq!(
+			parse_str("$Al + $Bo ->").unwrap_err(),
+			ParseError::ExpectedTokens(Span::at(12))
+		);
+	}
+
+	#[test]
+	fn reports_error_locations() {
+		let string = |s: &str| Token::StringLiteral(s.to_string());
+		let cases = [
+			("(1, 2", ParseError::ExpectedTokens(Span::at(5))),
+			("  ", ParseError::NoTokens(Span::at(2))),
+			(
+				"$$Ca(OH)2 + *",
+				ParseError::UnexpectedToken(Token::MulSign, Span::new(12, 13)),
+			),
+			(
+				"[13] + 1",
+				ParseError::UnexpectedToken(Token::Whitespace, Span::new(4, 5)),
+			),
+			(
+				"\"a\" \"b\"",
+				ParseError::UnexpectedToken(string("b"), Span::new(4, 7)),
+			),
+		];
+		for (input, error) in cases {
+			assert_eq!(parse_str(input).unwrap_err(), error, "{}", input);
+		}
+
+		let input = "$Al -> $Bo -> $Ch";
+		assert_eq!(
+			parse_str(input).unwrap_err().render(input),
+			"Line 1, column 12: Unexpected token '->'\n1 | $Al -> $Bo -> $Ch\n  |            ^^"
+		);
+	}
+
+	#[test]
+	fn recovers_from_errors() {
+		let p_table = PeriodicTable::new_alphabetic();
+		let mut dict = Dictionary::new();
+		dict.load_elements(&p_table);
+
+		let (expr, errors) = parse_str_recovering("(1 +, *, 3)");
+		assert_eq!(
+			errors,
+			[
+				ParseError::UnexpectedToken(Token::Comma, Span::new(4, 5)),
+				ParseError::UnexpectedToken(Token::MulSign, Span::new(6, 7)),
+			]
+		);
+		assert!(matches!(
+			expr.evaluate(&dict),
+			Err(EvaluationError::Parse(ParseError::UnexpectedToken(
+				Token::Comma,
+				_
+			)))
+		));
+
+		// an operation skips ahead to its next operator
+		let (expr, errors) = parse_str_recovering("1 + ? + 2 * (3 ?) - 4");
+		assert_eq!(
+			errors,
+			[
+				ParseError::UnexpectedToken(Token::Unknown("?".to_string()), Span::new(4, 5)),
+				ParseError::UnexpectedToken(Token::Unknown("?".to_string()), Span::new(15, 16)),
+			]
+		);
+		assert_eq!(expr.to_string(), "1 + ? + 2 * ? - 4");
+
+		let (_, errors) = parse_str_recovering("(1 2, 3");
+		assert_eq!(
+			errors,
+			[
+				ParseError::UnexpectedToken(Token::Integer("2".to_string()), Span::new(3, 4)),
+				ParseError::ExpectedTokens(Span::at(7)),
+			]
+		);
+
+		// the error is contained in the innermost tuple and the brackets within it are skipped
+		let (_, errors) = parse_str_recovering("((1 *), 2 (3, 4), 5)");
+		assert_eq!(
+			errors,
+			[
+				ParseError::UnexpectedToken(Token::RParen, Span::new(5, 6)),
+				ParseError::UnexpectedToken(Token::LParen, Span::new(10, 11)),
+			]
+		);
+
+		// a valid expression is unaffected
+		let (expr, errors) = parse_str_recovering("(1, 2) ");
+		assert!(errors.is_empty());
+		assert_eq!(
+			expr.evaluate(&dict).unwrap(),
+			Value::Tuple(vec![Value::Integer(1), Value::Integer(2)])
+		);
+	}
+
+	#[test]
+	fn parses_scripts() {
+		let (exprs, errors) = parse_script("1 + 1\n\n(*, 2)\n$H2 + ]\n");
+		assert_eq!(exprs.len(), 3);
+		assert_eq!(
+			errors,
+			[
+				ParseError::UnexpectedToken(Token::MulSign, Span::new(8, 9)),
+				ParseError::UnexpectedToken(Token::RBrack, Span::new(20, 21)),
+			]
+		);
+
+		// each line reports all of its errors, and a line break inside brackets doesn't end the line
+		let (exprs, errors) = parse_script("1 + ? + ?\n2 * *\n\n(3,\n 4) ]\n5");
+		let unknown = || Token::Unknown("?".to_string());
+		assert_eq!(
+			errors,
+			[
+				ParseError::UnexpectedToken(unknown(), Span::new(4, 5)),
+				ParseError::UnexpectedToken(unknown(), Span::new(8, 9)),
+				ParseError::UnexpectedToken(Token::MulSign, Span::new(14, 15)),
+				ParseError::UnexpectedToken(Token::RBrack, Span::new(25, 26)),
+			]
+		);
+		assert_eq!(exprs.len(), 4);
+		assert_eq!(exprs[0].to_string(), "1 + ? + ?");
+		assert_eq!(exprs[3], parse_str("5").unwrap());
+
+		let (exprs, errors) = parse_script("\n(1,\n  2) + (\n\t3)\n\n$He.mass\n");
+		assert!(errors.is_empty());
+		assert_eq!(
+			exprs,
+			[
+				parse_str("(1, 2) + 3").unwrap(),
+				parse_str("$He.mass").unwrap()
+			]
+		);
+	}
+}
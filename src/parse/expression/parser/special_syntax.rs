@@ -0,0 +1,344 @@
+use super::*;
+use crate::chem_data::{quantity::Quantity, smiles::Smiles};
+use std::{
+	collections::HashMap,
+	sync::{LazyLock, RwLock},
+};
+
+/// Creates the builder for what is between the braces of a `name!{...}` block. The builder is
+/// given every token up to and including the closing `}`, which it must reject to end the block.
+pub type SpecialSyntax = fn() -> Box<dyn ExpressionBuilder>;
+
+static SPECIAL_SYNTAXES: LazyLock<RwLock<HashMap<String, SpecialSyntax>>> = LazyLock::new(|| {
+	let builtins: [(&str, SpecialSyntax); 3] = [
+		("eqn", || Box::new(EqnSyntaxBuilder::new())),
+		("smiles", || Box::new(TextSyntaxBuilder::new(parse_smiles))),
+		("units", || Box::new(TextSyntaxBuilder::new(parse_quantity))),
+	];
+	let syntaxes = builtins
+		.into_iter()
+		.map(|(name, syntax)| (name.to_string(), syntax))
+		.collect();
+	RwLock::new(syntaxes)
+});
+
+/// Makes `name!{...}` parse with builders created by `syntax` from then on, replacing any syntax
+/// (including a built-in one) of the same name, which is returned. The built-in syntaxes are
+/// `eqn!{...}` for chemical equations, `smiles!{...}` for structures and `units!{...}` for
+/// quantities.
+pub fn register_special_syntax(name: &str, syntax: SpecialSyntax) -> Option<SpecialSyntax> {
+	SPECIAL_SYNTAXES
+		.write()
+		.expect("The special syntax registry shouldn't have been poisoned")
+		.insert(name.to_string(), syntax)
+}
+
+pub(super) fn lookup_special_syntax(name: &str) -> Option<SpecialSyntax> {
+	SPECIAL_SYNTAXES
+		.read()
+		.expect("The special syntax registry shouldn't have been poisoned")
+		.get(name)
+		.copied()
+}
+
+/// Builds a chemical equation whose species are condensed formulas written without `$$`, e.g.
+/// `eqn!{2H2 + O2 -> 2H2O}` or `eqn!{Cu(OH)2 <-> Cu+2 + 2OH-}`
+struct EqnSyntaxBuilder {
+	inner_expr: Box<dyn ExpressionBuilder>,
+	// whether the next identifier, `(` or `[` starts a species rather than continuing one
+	at_species_start: bool,
+	seen_arrow: bool,
+}
+
+impl EqnSyntaxBuilder {
+	fn new() -> Self {
+		Self {
+			inner_expr: Box::new(WrapperExprBuilder::new()),
+			at_species_start: true,
+			seen_arrow: false,
+		}
+	}
+}
+
+impl ExpressionBuilder for EqnSyntaxBuilder {
+	fn add_token(
+		&mut self,
+		token: Token,
+		span: Span,
+		errors: &mut Vec<ParseError>,
+	) -> Result<Option<Token>, ParseError> {
+		match token {
+			Token::Identifier(_) | Token::LParen | Token::LBrack if self.at_species_start => {
+				// start the species as if it had been written with `$$`
+				self.at_species_start = false;
+				let cash =
+					self.inner_expr
+						.add_token(Token::CashCash, Span::at(span.start), errors)?;
+				if cash.is_some() {
+					return Err(ParseError::UnexpectedToken(token, span));
+				}
+			},
+			// a coefficient comes before the species it belongs to
+			Token::Whitespace | Token::Integer(_) => (),
+			Token::PlusSign => self.at_species_start = true,
+			Token::Arrow | Token::ReversibleArrow => {
+				self.at_species_start = true;
+				self.seen_arrow = true;
+			},
+			Token::RCurly if !self.seen_arrow => {
+				return Err(ParseError::UnexpectedToken(token, span))
+			},
+			_ => self.at_species_start = false,
+		}
+		self.inner_expr.add_token(token, span, errors)
+	}
+
+	fn finish(
+		self: Box<Self>,
+		end: Span,
+		errors: &mut Vec<ParseError>,
+	) -> Result<Expression, ParseError> {
+		self.inner_expr.finish(end, errors)
+	}
+}
+
+/// Reads the text of a special syntax block into an expression, or returns a message describing
+/// what is wrong with the text along with the char offset into the text where it went wrong
+type TextSyntaxParser = fn(&str) -> Result<Expression, (String, usize)>;
+
+/// Collects what is between the braces as text, for syntaxes whose contents aren't expressions,
+/// e.g. `smiles!{c1ccccc1}`. Leading and trailing whitespace is ignored.
+struct TextSyntaxBuilder {
+	text: String,
+	// where in the source each char of the text came from
+	positions: Vec<usize>,
+	// where the text ended, i.e. the position of the closing `}`
+	end: usize,
+	closed: bool,
+	parse: TextSyntaxParser,
+}
+
+impl TextSyntaxBuilder {
+	fn new(parse: TextSyntaxParser) -> Self {
+		Self {
+			text: String::new(),
+			positions: Vec::new(),
+			end: 0,
+			closed: false,
+			parse,
+		}
+	}
+}
+
+impl ExpressionBuilder for TextSyntaxBuilder {
+	fn add_token(
+		&mut self,
+		token: Token,
+		span: Span,
+		_errors: &mut Vec<ParseError>,
+	) -> Result<Option<Token>, ParseError> {
+		if self.closed {
+			return Ok(Some(token));
+		}
+
+		match token {
+			Token::RCurly => {
+				self.closed = true;
+				self.end = span.start;
+				Ok(Some(token))
+			},
+			// the block can't tell a nested `}` from its own
+			Token::LCurly => Err(ParseError::UnexpectedToken(token, span)),
+			token => {
+				// the token is written as it was in the source, except that whitespace is
+				// collapsed, so the positions of its chars only drift within its span
+				for (i, c) in token.to_string().chars().enumerate() {
+					self.text.push(c);
+					self.positions
+						.push((span.start + i).min(span.end.saturating_sub(1)));
+				}
+				self.end = span.end;
+				Ok(None)
+			},
+		}
+	}
+
+	fn finish(
+		self: Box<Self>,
+		_end: Span,
+		_errors: &mut Vec<ParseError>,
+	) -> Result<Expression, ParseError> {
+		let leading = self.text.chars().take_while(|c| c.is_whitespace()).count();
+		let text = self.text.trim();
+		(self.parse)(text).map_err(|(message, offset)| {
+			let span = match self.positions.get(leading + offset) {
+				Some(&position) if offset < text.chars().count() => {
+					Span::new(position, position + 1)
+				},
+				_ => Span::at(self.end),
+			};
+			ParseError::InvalidSpecialSyntax(message, span)
+		})
+	}
+}
+
+fn parse_smiles(text: &str) -> Result<Expression, (String, usize)> {
+	match text.parse::<Smiles>() {
+		Ok(smiles) => Ok(Expression::Smiles(SmilesExpr {
+			smiles,
+			text: text.to_string(),
+		})),
+		Err(error) => Err((error.to_string(), error.position())),
+	}
+}
+
+fn parse_quantity(text: &str) -> Result<Expression, (String, usize)> {
+	match text.parse::<Quantity>() {
+		Ok(quantity) => Ok(Expression::Quantity(QuantityExpr {
+			quantity,
+			text: text.to_string(),
+		})),
+		Err(error) => Err((error.to_string(), error.position())),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		chem_data::{chem_eqn::ChemEqn, dictionary::Dictionary, elements::PeriodicTable},
+		parse::expression::tests::evaluate,
+	};
+
+	#[test]
+	fn parses_builtin_syntaxes() {
+		let p_table = PeriodicTable::standard();
+		let mut dict = Dictionary::new();
+		dict.load_elements(&p_table);
+
+		let equation = |string: &str| match evaluate(&dict, string) {
+			Ok(Value::ChemEqn(eqn)) => eqn,
+			other => panic!("{} should've been an equation, not {:?}", string, other),
+		};
+		let same_equation = |bare: &str, written: &str| {
+			let expected: ChemEqn = equation(written);
+			assert_eq!(equation(bare), expected, "{}", bare);
+		};
+		same_equation("eqn!{2H2 + O2 -> 2H2O}", "2$H2 + $O2 -> 2$H2O");
+		same_equation(
+			"eqn!{ Cu(OH)2 <-> Cu+2 + 2OH- }",
+			"$$Cu(OH)2 <-> $$Cu+2 + 2$$OH-",
+		);
+		same_equation("eqn!{[13]CO2 -> [13]C + O2}", "$$[13]CO2 -> $$[13]C + $O2");
+		same_equation(
+			"eqn!{CuSO4.5H2O -> CuSO4 + 5H2O}",
+			"$$CuSO4·5H2O -> $CuSO4 + 5$H2O",
+		);
+
+		let formula = |string: &str| evaluate(&dict, string).unwrap().to_string();
+		assert_eq!(formula("smiles!{CC(=O)O}"), formula("$C2H4O2"));
+		assert_eq!(formula("smiles!{[NH4+]}"), formula("$NH4+"));
+		assert_eq!(formula("smiles!{c1ccccc1}.mass"), formula("$C6H6.mass"));
+
+		assert_eq!(
+			evaluate(&dict, "units!{18.02 g/mol} * units!{2.000 mol}").unwrap(),
+			Value::Quantity("36.04 g".parse().unwrap())
+		);
+		assert_eq!(
+			evaluate(&dict, "units!{ 9.81 m/s^2 }.units").unwrap(),
+			Value::String("m/s^2".to_string())
+		);
+		assert!(matches!(
+			evaluate(&dict, "units!{1.0 g} + units!{1.0 mol}"),
+			Err(EvaluationError::Units(_))
+		));
+		for string in [
+			"units!{1 m^2000000000} * units!{1 m^2000000000}",
+			"units!{1 m^-2147483647} / units!{1 m^2}",
+			"units!{1 m^2000000000} ^ 2",
+		] {
+			assert!(
+				matches!(evaluate(&dict, string), Err(EvaluationError::Overflow)),
+				"{}",
+				string
+			);
+		}
+	}
+
+	#[test]
+	fn reports_errors_in_special_syntax() {
+		let cases = [
+			(
+				"nope!{1}",
+				ParseError::UnknownSpecialSyntax("nope".to_string(), Span::new(4, 5)),
+			),
+			(
+				"eqn!{H2 + O2}",
+				ParseError::UnexpectedToken(Token::RCurly, Span::new(12, 13)),
+			),
+			(
+				"smiles!{C1CC}",
+				ParseError::InvalidSpecialSyntax(
+					"Ring bond 1 is never closed".to_string(),
+					Span::new(9, 10),
+				),
+			),
+			(
+				"units!{2.0 g/}",
+				ParseError::InvalidSpecialSyntax(
+					"Unexpected end of units".to_string(),
+					Span::at(13),
+				),
+			),
+			("smiles!{C", ParseError::ExpectedTokens(Span::at(9))),
+		];
+		for (input, error) in cases {
+			assert_eq!(parse_str(input).unwrap_err(), error, "{}", input);
+		}
+
+		// an unknown syntax is skipped as a whole
+		let (_, errors) = parse_str_recovering("(nope!{(1, 2)}, 3 *)");
+		assert_eq!(
+			errors,
+			[
+				ParseError::UnknownSpecialSyntax("nope".to_string(), Span::new(5, 6)),
+				ParseError::UnexpectedToken(Token::RParen, Span::new(19, 20)),
+			]
+		);
+	}
+
+	#[test]
+	fn registers_custom_syntaxes() {
+		/// Counts the tokens between the braces, e.g. `count_tokens!{a + b}` is 5
+		struct CountTokens(u32);
+		impl ExpressionBuilder for CountTokens {
+			fn add_token(
+				&mut self,
+				token: Token,
+				_span: Span,
+				_errors: &mut Vec<ParseError>,
+			) -> Result<Option<Token>, ParseError> {
+				if token == Token::RCurly {
+					return Ok(Some(token));
+				}
+				self.0 += 1;
+				Ok(None)
+			}
+
+			fn finish(
+				self: Box<Self>,
+				_end: Span,
+				_errors: &mut Vec<ParseError>,
+			) -> Result<Expression, ParseError> {
+				Ok(Expression::Integer(IntegerLiteral { value: self.0 }))
+			}
+		}
+
+		assert!(register_special_syntax("count_tokens", || Box::new(CountTokens(0))).is_none());
+		let dict = Dictionary::new();
+		assert_eq!(
+			evaluate(&dict, "count_tokens!{a + b} * 2").unwrap(),
+			Value::Integer(10)
+		);
+	}
+}
@@ -0,0 +1,276 @@
+use super::*;
+use std::fmt::{Display, Formatter, Result};
+
+/// Writes the expression as canonical source: binary operators other than `.` are surrounded by
+/// single spaces, commas are followed by one, and only the parentheses that precedence requires
+/// are written. Formulas keep their `$` or `$$` form, with adducts written with `·`. An error
+/// placeholder is written as `?`, so the source still fails to parse.
+impl Display for Expression {
+	fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+		match self {
+			Expression::Identifier(identifier) => write_identifier(f, &identifier.name),
+			Expression::Isotope(expr) => write!(f, "[{}]{}", expr.mass_number, expr.element),
+			Expression::String(expr) => write!(f, "\"{}\"", expr.content),
+			Expression::Integer(expr) => write!(f, "{}", expr.value),
+			Expression::Real(expr) => write!(f, "{}", expr.value),
+			Expression::Tuple(expr) => {
+				write!(f, "(")?;
+				write_list(f, &expr.values)?;
+				// a lone value needs a trailing comma to be a tuple rather than a grouping
+				if expr.values.len() == 1 {
+					write!(f, ",")?;
+				}
+				write!(f, ")")
+			},
+			Expression::Call(expr) => {
+				write_identifier(f, &expr.name)?;
+				write!(f, "(")?;
+				write_list(f, &expr.args)?;
+				write!(f, ")")
+			},
+			Expression::Infix(expr) => {
+				write_operand(f, &expr.lhs, expr.operator, false)?;
+				match expr.operator {
+					InfixOperator::FunctionCall => write!(f, ".")?,
+					operator => write!(f, " {} ", operator.symbol())?,
+				}
+				write_operand(f, &expr.rhs, expr.operator, true)
+			},
+			Expression::MolecularFormula(expr) => {
+				write!(f, "$")?;
+				for symbol_or_subscript in &expr.symbols_and_subscripts {
+					write!(f, "{}", symbol_or_subscript)?;
+				}
+				write_charge(f, &expr.charge)
+			},
+			Expression::CondensedFormula(expr) => {
+				write!(f, "$$")?;
+				write_parts(f, &expr.parts)?;
+				write_charge(f, &expr.charge)
+			},
+			Expression::Coefficient(expr) => write!(f, "{}{}", expr.coefficient, expr.species),
+			Expression::ChemEqn(expr) => {
+				write_equation_side(f, &expr.reactants)?;
+				write!(f, " {} ", if expr.reversible { "<->" } else { "->" })?;
+				write_equation_side(f, &expr.products)
+			},
+			Expression::Smiles(expr) => write!(f, "smiles!{{{}}}", expr.text),
+			Expression::Quantity(expr) => write!(f, "units!{{{}}}", expr.text),
+			Expression::Error(_) => write!(f, "?"),
+		}
+	}
+}
+
+/// Writes the name as an identifier, quoting it with a leading `'` if it wouldn't otherwise be
+/// read back as one, e.g. `'pH7`. Uppercase letters are only allowed at the start or right after
+/// an underscore, or they would start a separate identifier.
+fn write_identifier(f: &mut Formatter<'_>, name: &str) -> Result {
+	let mut prev_char = None;
+	let is_plain = !name.is_empty()
+		&& name.chars().all(|c| {
+			let is_valid = match prev_char {
+				None | Some('_') => c.is_ascii_alphabetic() || c == '_',
+				Some(_) => c.is_ascii_lowercase() || c == '_',
+			};
+			prev_char = Some(c);
+			is_valid
+		});
+	if is_plain {
+		write!(f, "{}", name)
+	} else {
+		write!(f, "'{}", name)
+	}
+}
+
+fn write_list(f: &mut Formatter<'_>, exprs: &[Expression]) -> Result {
+	for (i, expr) in exprs.iter().enumerate() {
+		if i > 0 {
+			write!(f, ", ")?;
+		}
+		write!(f, "{}", expr)?;
+	}
+	Ok(())
+}
+
+/// Writes an operand of the operator, parenthesized if it wouldn't otherwise be read back as one
+fn write_operand(
+	f: &mut Formatter<'_>,
+	operand: &Expression,
+	operator: InfixOperator,
+	is_rhs: bool,
+) -> Result {
+	if needs_parentheses(operand, operator, is_rhs) {
+		write!(f, "({})", operand)
+	} else {
+		write!(f, "{}", operand)
+	}
+}
+
+fn needs_parentheses(operand: &Expression, operator: InfixOperator, is_rhs: bool) -> bool {
+	match operand {
+		// an equation takes in everything around it
+		Expression::ChemEqn(_) => true,
+		Expression::Infix(inner) => {
+			let (inner, outer) = (inner.operator.precedence(), operator.precedence());
+			// an operand on the side that the operator associates away from can't have the same
+			// precedence, e.g. the rhs of `1 - (2 - 3)` or the lhs of `(2 ^ 3) ^ 2`
+			inner < outer || (inner == outer && is_rhs != operator.is_right_associative())
+		},
+		_ => false,
+	}
+}
+
+fn write_charge(f: &mut Formatter<'_>, charge: &Option<ChargeExpr>) -> Result {
+	let Some(charge) = charge else {
+		return Ok(());
+	};
+	write!(f, "{}", if charge.is_positive { "+" } else { "-" })?;
+	match &charge.magnitude {
+		None => Ok(()),
+		Some(magnitude) => write!(f, "{}", magnitude),
+	}
+}
+
+fn write_parts(f: &mut Formatter<'_>, parts: &[CondensedPartExpr]) -> Result {
+	let write_count = |f: &mut Formatter<'_>, count: u32| {
+		if count == 1 {
+			Ok(())
+		} else {
+			write!(f, "{}", count)
+		}
+	};
+	for part in parts {
+		match part {
+			CondensedPartExpr::Element(element, subscript) => {
+				write!(f, "{}", element)?;
+				write_count(f, *subscript)?;
+			},
+			CondensedPartExpr::Group(parts, subscript) => {
+				write!(f, "(")?;
+				write_parts(f, parts)?;
+				write!(f, ")")?;
+				write_count(f, *subscript)?;
+			},
+			CondensedPartExpr::Adduct(coefficient, parts) => {
+				write!(f, "·")?;
+				write_count(f, *coefficient)?;
+				write_parts(f, parts)?;
+			},
+		}
+	}
+	Ok(())
+}
+
+fn write_equation_side(f: &mut Formatter<'_>, side: &EquationSide) -> Result {
+	for (i, (coefficient, species)) in side.iter().enumerate() {
+		if i > 0 {
+			write!(f, " + ")?;
+		}
+		if *coefficient != 1 {
+			write!(f, "{}{}", coefficient, species)?;
+		} else if needs_parentheses_as_species(species, side.len()) {
+			write!(f, "({})", species)?;
+		} else {
+			write!(f, "{}", species)?;
+		}
+	}
+	Ok(())
+}
+
+/// Whether the species would be read back as something else if it weren't parenthesized, e.g. as
+/// several species or with a coefficient
+fn needs_parentheses_as_species(species: &Expression, side_len: usize) -> bool {
+	match species {
+		Expression::ChemEqn(_) | Expression::Coefficient(_) => true,
+		// a side is split into species only if it is a sum, and then it is split at every `+`
+		Expression::Infix(_) => side_len > 1 || is_sum(species),
+		_ => false,
+	}
+}
+
+/// Whether the expression is written as a chain of `+` operations, outside of any parentheses
+fn is_sum(expr: &Expression) -> bool {
+	match expr {
+		Expression::Infix(infix) if infix.operator == InfixOperator::Plus => {
+			[(&infix.lhs, false), (&infix.rhs, true)]
+				.into_iter()
+				.all(|(operand, is_rhs)| {
+					!matches!(**operand, Expression::Infix(_))
+						|| needs_parentheses(operand, InfixOperator::Plus, is_rhs)
+						|| is_sum(operand)
+				})
+		},
+		_ => false,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn prints_canonical_source() {
+		let cases = [
+			("1+2*3", "1 + 2 * 3"),
+			("(1 + 2) * 3", "(1 + 2) * 3"),
+			("((1 + 2)) + (3 * 4)", "1 + 2 + 3 * 4"),
+			("1 - (2 - 3)", "1 - (2 - 3)"),
+			("(1 - 2) - 3", "1 - 2 - 3"),
+			("2 ^ (3 ^ 2)", "2 ^ 3 ^ 2"),
+			("(2 ^ 3) ^ 2", "(2 ^ 3) ^ 2"),
+			("( $H2O ).mass * 2", "$H2O.mass * 2"),
+			("(1 + 2).count( 3 , 4 )", "(1 + 2).count(3, 4)"),
+			("( 1 , ( 2 , ) , ( ) )", "(1, (2,), ())"),
+			("mass_number", "mass_number"),
+			("Fe_Cl + _x_", "Fe_Cl + _x_"),
+			("'mass_Number2", "'mass_Number2"),
+			("'pH7", "'pH7"),
+			("\"hi there\"", "\"hi there\""),
+			("1.50e3 / 2", "1.50e3 / 2"),
+			("$[13]CH4 + $NH4+ + $SO4-2", "$[13]CH4 + $NH4+ + $SO4-2"),
+			("$$CuSO4.5H2O", "$$CuSO4·5H2O"),
+			("$$Fe(CN)6-3 * 2", "$$Fe(CN)6-3 * 2"),
+			("2$H2  +  $O2->2$H2O", "2$H2 + $O2 -> 2$H2O"),
+			("($A + $B) <=> $C", "($A + $B) <-> $C"),
+			("(2$A) + $B -> $C", "(2$A) + $B -> $C"),
+			("($B.hill) + $A -> $C", "($B.hill) + $A -> $C"),
+			// a chain that isn't just a sum is a single species
+			("(2$A) + $B.hill -> $C", "2$A + $B.hill -> $C"),
+			("$A.hill -> $C", "$A.hill -> $C"),
+			("($A -> $B) + 1", "($A -> $B) + 1"),
+			("eqn!{ 2H2 + O2 -> 2H2O }", "2$$H2 + $$O2 -> 2$$H2O"),
+			("smiles!{ CC(=O)O }", "smiles!{CC(=O)O}"),
+			("units!{9.81 m/s^2} * 2", "units!{9.81 m/s^2} * 2"),
+		];
+		for (source, printed) in cases {
+			let expr = parse_str(source).unwrap();
+			assert_eq!(expr.to_string(), printed, "{}", source);
+			assert_eq!(parse_str(printed).unwrap(), expr, "{}", source);
+		}
+	}
+
+	#[test]
+	fn prints_rewritten_expressions() {
+		let integer = |value| Expression::Integer(IntegerLiteral { value });
+		let infix = |lhs, operator, rhs| {
+			Expression::Infix(InfixOperationExpr {
+				lhs: Box::new(lhs),
+				operator,
+				rhs: Box::new(rhs),
+			})
+		};
+
+		let sum = infix(integer(1), InfixOperator::Plus, integer(2));
+		let expr = infix(integer(3), InfixOperator::Mul, sum.clone());
+		assert_eq!(expr.to_string(), "3 * (1 + 2)");
+		assert_eq!(parse_str(&expr.to_string()).unwrap(), expr);
+
+		let expr = infix(sum.clone(), InfixOperator::Plus, sum);
+		assert_eq!(expr.to_string(), "1 + 2 + (1 + 2)");
+		assert_eq!(parse_str(&expr.to_string()).unwrap(), expr);
+
+		let (expr, _) = parse_str_recovering("(1, 2 +)");
+		assert_eq!(expr.to_string(), "(1, 2 + ?)");
+		assert!(parse_str(&expr.to_string()).is_err());
+	}
+}
@@ -0,0 +1,108 @@
+/// A range of the source text, counted in chars (not bytes) from the start of the text. The end is
+/// exclusive, so an empty span marks a position between two chars, e.g. the end of the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+	pub start: usize,
+	pub end: usize,
+}
+
+impl Span {
+	pub fn new(start: usize, end: usize) -> Self {
+		Self { start, end }
+	}
+
+	/// An empty span at the position
+	pub fn at(position: usize) -> Self {
+		Self::new(position, position)
+	}
+
+	/// The smallest span covering both spans
+	pub fn to(self, other: Span) -> Self {
+		Self::new(self.start.min(other.start), self.end.max(other.end))
+	}
+
+	pub fn len(&self) -> usize {
+		self.end.saturating_sub(self.start)
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+}
+
+/// Returns the 1-based line and column of the position in the source
+pub fn line_and_column(source: &str, position: usize) -> (usize, usize) {
+	let mut line = 1;
+	let mut column = 1;
+	for c in source.chars().take(position) {
+		if c == '\n' {
+			line += 1;
+			column = 1;
+		} else {
+			column += 1;
+		}
+	}
+	(line, column)
+}
+
+/// Shows the line of the source containing the start of the span with a caret underline beneath
+/// the span, e.g.
+/// ```text
+/// 1 | $Al -> $Bo -> $Ch
+///   |            ^^
+/// ```
+/// A span reaching past the end of its line is only underlined to the end of the line, and an
+/// empty span gets a single caret.
+pub fn underline(source: &str, span: Span) -> String {
+	let (line_number, column) = line_and_column(source, span.start);
+	let line = source
+		.split('\n')
+		.nth(line_number - 1)
+		.unwrap_or_default()
+		.trim_end_matches('\r');
+
+	// keep tabs in the padding so the carets line up however wide the tabs are displayed
+	let padding: String = line
+		.chars()
+		.take(column - 1)
+		.map(|c| if c == '\t' { '\t' } else { ' ' })
+		.collect();
+	let line_length = line.chars().count();
+	let carets = span
+		.len()
+		.min(line_length.saturating_sub(column - 1))
+		.max(1);
+
+	let gutter = " ".repeat(line_number.to_string().len());
+	format!(
+		"{} | {}\n{} | {}{}",
+		line_number,
+		line,
+		gutter,
+		padding,
+		"^".repeat(carets)
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn underlines_spans() {
+		assert_eq!(
+			underline("$Al -> $Bo -> $Ch", Span::new(11, 13)),
+			"1 | $Al -> $Bo -> $Ch\n  |            ^^"
+		);
+		// the end of the input
+		assert_eq!(underline("(1, 2", Span::at(5)), "1 | (1, 2\n  |      ^");
+		// later lines, with tabs kept in the padding
+		assert_eq!(
+			underline("a\n\tb + ?\nc", Span::new(7, 8)),
+			"2 | \tb + ?\n  | \t    ^"
+		);
+		assert_eq!(line_and_column("a\n\tb + ?\nc", 7), (2, 6));
+		// only the first line of a span is underlined
+		assert_eq!(underline("ab\ncd", Span::new(1, 4)), "1 | ab\n  |  ^");
+	}
+}
@@ -1,9 +1,13 @@
+use super::span::Span;
 use crate::helper::peek_iter::PeekIter;
+use std::fmt::Display;
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Token {
 	Unknown(String),
 	Whitespace,
+	/// Whitespace that includes a line break
+	Newline,
 	Identifier(String),
 	StringLiteral(String),
 	Integer(String),
@@ -27,9 +31,17 @@ pub enum Token {
 	Comma,
 	Colon,
 	Arrow,
+	ReversibleArrow,
 	Ellipse,
 }
 
+/// A token along with where it came from in the source
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SpannedToken {
+	pub token: Token,
+	pub span: Span,
+}
+
 type StrTokPair = (&'static str, Token);
 
 static TOKEN_STRINGS: &[StrTokPair] = &[
@@ -52,23 +64,68 @@ static TOKEN_STRINGS: &[StrTokPair] = &[
 	(",", Token::Comma),
 	(":", Token::Colon),
 	("->", Token::Arrow),
+	("<->", Token::ReversibleArrow),
+	("<=>", Token::ReversibleArrow),
 	("...", Token::Ellipse),
 ];
 
-/// An iterator adaptor on an Iterator<Item = char> that tokenizes the items
+/// Displays the token as it would be written, e.g. `->` or `"string"`
+impl Display for Token {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Token::Unknown(string)
+			| Token::Identifier(string)
+			| Token::Integer(string)
+			| Token::Real(string) => write!(f, "{}", string),
+			Token::StringLiteral(string) => write!(f, "\"{}\"", string),
+			Token::Whitespace => write!(f, " "),
+			Token::Newline => writeln!(f),
+			token => {
+				let (pattern, _) = TOKEN_STRINGS
+					.iter()
+					.find(|(_, other)| other == token)
+					.expect("Every other token should've had a pattern");
+				write!(f, "{}", pattern)
+			},
+		}
+	}
+}
+
+/// An iterator adaptor on an Iterator<Item = char> that tokenizes the items, along with the span of
+/// chars that each token came from
 pub struct Tokens<I: Iterator<Item = char>> {
 	source: PeekIter<I>,
 	token_strings: &'static [StrTokPair],
+	// the number of chars consumed so far
+	position: usize,
 }
 
 impl<I: Iterator<Item = char>> Tokens<I> {
 	pub fn new(source: I) -> Self {
 		Tokens {
 			source: PeekIter::new(source),
-			token_strings: &TOKEN_STRINGS,
+			token_strings: TOKEN_STRINGS,
+			position: 0,
 		}
 	}
 
+	/// The number of chars consumed so far, i.e. the end of the last token
+	pub fn position(&self) -> usize {
+		self.position
+	}
+
+	fn next_char(&mut self) -> Option<char> {
+		let next_char = self.source.next()?;
+		self.position += 1;
+		Some(next_char)
+	}
+
+	fn next_char_if(&mut self, predicate: impl FnOnce(&char) -> bool) -> Option<char> {
+		let next_char = self.source.next_if(predicate)?;
+		self.position += 1;
+		Some(next_char)
+	}
+
 	fn check_match(&mut self, pattern: &str) -> bool {
 		for (i, pattern_char) in pattern.chars().enumerate() {
 			match self.source.peek(i) {
@@ -92,17 +149,38 @@ impl<I: Iterator<Item = char>> Tokens<I> {
 			.filter(|(pattern, _)| self.check_match(pattern))
 			.max_by(|(str_a, _), (str_b, _)| str_a.cmp(str_b))?;
 		for _ in 0..longest_matching_pattern.len() {
-			self.source.next();
+			self.next_char();
 		}
 		Some(token.clone())
 	}
 
+	/// Whether the next char is a decimal point rather than a dot before a method name, e.g.
+	/// `$H2SO4.mass`, or before an adduct of a condensed formula, e.g. `CuSO4.5H2O`
+	fn at_decimal_point(&mut self) -> bool {
+		if self.source.peek(0) != Some(&'.') {
+			return false;
+		}
+		let mut index = 1;
+		while self.source.peek(index).is_some_and(char::is_ascii_digit) {
+			index += 1;
+		}
+		// an exponent may directly follow the decimal point, e.g. `1.e5`
+		let starts_exponent = matches!(self.source.peek(index), Some('e' | 'E'))
+			&& matches!(self.source.peek(index + 1), Some('0'..='9' | '-' | '+'));
+		match self.source.peek(index) {
+			_ if starts_exponent => true,
+			Some(c) if c.is_ascii_uppercase() || *c == '(' || *c == '[' => false,
+			Some(c) => index > 1 || !c.is_ascii_alphabetic(),
+			None => true,
+		}
+	}
+
 	/// Returns the longest possible String from the next characters in the stream and removes those
 	/// characters. Uses the function valid_char to determine whether a character is a valid
 	/// character for the string. Returns an empty String if no characters constitute a valid token.
 	fn get_longest_valid_string(&mut self, mut is_valid_char: impl FnMut(&char) -> bool) -> String {
 		let mut result = String::new();
-		while let Some(next_char) = self.source.next_if(&mut is_valid_char) {
+		while let Some(next_char) = self.next_char_if(&mut is_valid_char) {
 			result.push(next_char);
 		}
 		result
@@ -110,63 +188,82 @@ impl<I: Iterator<Item = char>> Tokens<I> {
 }
 
 impl<I: Iterator<Item = char>> Iterator for Tokens<I> {
-	type Item = Token;
+	type Item = SpannedToken;
 
 	fn next(&mut self) -> Option<Self::Item> {
-		// underscores are not part of the tokenization
-		while self.source.next_if(|c| *c == '_').is_some() {}
+		let start = self.position;
+		let token = self.next_token()?;
+		Some(SpannedToken {
+			token,
+			span: Span::new(start, self.position),
+		})
+	}
+}
 
+impl<I: Iterator<Item = char>> Tokens<I> {
+	fn next_token(&mut self) -> Option<Token> {
 		// check the token type by peeking the next character
 		let peek_char = self.source.peek(0)?;
 		if peek_char.is_ascii_whitespace() {
-			self.get_longest_valid_string(char::is_ascii_whitespace);
-			Some(Token::Whitespace)
-		} else if peek_char.is_ascii_alphabetic() {
-			let mut is_first_char = true;
+			let whitespace = self.get_longest_valid_string(char::is_ascii_whitespace);
+			Some(if whitespace.contains('\n') {
+				Token::Newline
+			} else {
+				Token::Whitespace
+			})
+		} else if peek_char.is_ascii_alphabetic() || *peek_char == '_' {
+			// an uppercase letter starts a new identifier, e.g. each symbol of `$H2SO4`, unless it
+			// follows an underscore, e.g. `molar_Mass`
+			let mut prev_char = None;
 			Some(Token::Identifier(self.get_longest_valid_string(|c| {
-				if is_first_char {
-					is_first_char = false;
-					return true; // already checked that the first character is valid
-				}
-				c.is_ascii_lowercase()
+				let is_valid = match prev_char {
+					None | Some('_') => c.is_ascii_alphabetic() || *c == '_',
+					Some(_) => c.is_ascii_lowercase() || *c == '_',
+				};
+				prev_char = Some(*c);
+				is_valid
 			})))
 		} else if *peek_char == '\'' {
-			self.source.next(); // discard the apostrophe
+			self.next_char(); // discard the apostrophe
 			Some(Token::Identifier(self.get_longest_valid_string(|c| {
-				c.is_ascii_alphabetic() || c.is_ascii_digit()
+				c.is_ascii_alphanumeric() || *c == '_'
 			})))
 		} else if *peek_char == '\"' {
-			self.source.next(); // discard the opening quotation
+			self.next_char(); // discard the opening quotation
 			let string = self.get_longest_valid_string(|c| *c != '\"');
-			self.source.next(); // discard the closing quotation
+			self.next_char(); // discard the closing quotation
 			Some(Token::StringLiteral(string))
 		} else if peek_char.is_ascii_digit() {
-			let mut seen_decimal = false;
+			let mut number_string = self.get_longest_valid_string(char::is_ascii_digit);
+
+			if !self.at_decimal_point() {
+				return Some(Token::Integer(number_string));
+			}
+			number_string.push(
+				self.next_char()
+					.expect("Should've peeked the decimal point"),
+			);
+
 			let mut seen_exp = false;
-			let number_string = self.get_longest_valid_string(|c| {
+			let mut prev_was_exp = false;
+			number_string.push_str(&self.get_longest_valid_string(|c| {
+				let was_exp = std::mem::replace(&mut prev_was_exp, false);
 				if c.is_ascii_digit() {
 					true
-				} else if !seen_decimal && *c == '.' {
-					seen_decimal = true;
-					true
-				} else if seen_decimal && !seen_exp && *c == 'e' {
+				} else if !seen_exp && (*c == 'e' || *c == 'E') {
 					seen_exp = true;
+					prev_was_exp = true;
 					true
 				} else {
-					false
+					// the exponent may be signed, e.g. `6.02e-23`
+					was_exp && (*c == '-' || *c == '+')
 				}
-			});
-
-			if seen_decimal {
-				Some(Token::Real(number_string))
-			} else {
-				Some(Token::Integer(number_string))
-			}
+			}));
+			Some(Token::Real(number_string))
 		} else {
 			match self.get_longest_simple_token() {
 				None => Some(Token::Unknown(
-					self.source
-						.next()
+					self.next_char()
 						.expect("Another char should've existed")
 						.to_string(),
 				)),
@@ -198,12 +295,9 @@ mod tests {
 	#[test]
 	fn tokenizes_properly() {
 		let input = "'notregu1ar_idEnt1-fier*=-->(< ....caLiFor_ni-aGur!$$$123 .56.4e2 1.234.a ? ";
-		let tokens_are: Vec<Token> = input.chars().into_token_iter().collect();
+		let tokens_are: Vec<Token> = input.chars().into_token_iter().map(|t| t.token).collect();
 		let tokens_should_be = vec![
-			Identifier("notregu1ar".to_string()),
-			Identifier("id".to_string()),
-			Identifier("Ent".to_string()),
-			Integer("1".to_string()),
+			Identifier("notregu1ar_idEnt1".to_string()),
 			MinusSign,
 			Identifier("fier".to_string()),
 			MulSign,
@@ -217,8 +311,7 @@ mod tests {
 			Dot,
 			Identifier("ca".to_string()),
 			Identifier("Li".to_string()),
-			Identifier("For".to_string()),
-			Identifier("ni".to_string()),
+			Identifier("For_ni".to_string()),
 			MinusSign,
 			Identifier("a".to_string()),
 			Identifier("Gur".to_string()),
@@ -240,4 +333,140 @@ mod tests {
 
 		assert_eq!(tokens_are, tokens_should_be);
 	}
+
+	#[test]
+	fn tokenizes_signed_exponents() {
+		let input = "6.02e-23 1.0e+5 1.e5 1.E-5 2e-3";
+		let tokens_are: Vec<Token> = input.chars().into_token_iter().map(|t| t.token).collect();
+		let tokens_should_be = vec![
+			Real("6.02e-23".to_string()),
+			Whitespace,
+			Real("1.0e+5".to_string()),
+			Whitespace,
+			Real("1.e5".to_string()),
+			Whitespace,
+			Real("1.E-5".to_string()),
+			Whitespace,
+			Integer("2".to_string()),
+			Identifier("e".to_string()),
+			MinusSign,
+			Integer("3".to_string()),
+		];
+
+		assert_eq!(tokens_are, tokens_should_be);
+	}
+
+	#[test]
+	fn tokenizes_method_calls_after_numbers() {
+		let input = "$SO4.mass 100. 2.5.a 3.e CuSO4.5H2O X2.[13]C 2.5E3";
+		let tokens_are: Vec<Token> = input.chars().into_token_iter().map(|t| t.token).collect();
+		let tokens_should_be = vec![
+			Cash,
+			Identifier("S".to_string()),
+			Identifier("O".to_string()),
+			Integer("4".to_string()),
+			Dot,
+			Identifier("mass".to_string()),
+			Whitespace,
+			Real("100.".to_string()),
+			Whitespace,
+			Real("2.5".to_string()),
+			Dot,
+			Identifier("a".to_string()),
+			Whitespace,
+			Integer("3".to_string()),
+			Dot,
+			Identifier("e".to_string()),
+			Whitespace,
+			Identifier("Cu".to_string()),
+			Identifier("S".to_string()),
+			Identifier("O".to_string()),
+			Integer("4".to_string()),
+			Dot,
+			Integer("5".to_string()),
+			Identifier("H".to_string()),
+			Integer("2".to_string()),
+			Identifier("O".to_string()),
+			Whitespace,
+			Identifier("X".to_string()),
+			Integer("2".to_string()),
+			Dot,
+			LBrack,
+			Integer("13".to_string()),
+			RBrack,
+			Identifier("C".to_string()),
+			Whitespace,
+			Real("2.5E3".to_string()),
+		];
+
+		assert_eq!(tokens_are, tokens_should_be);
+	}
+
+	#[test]
+	fn tracks_spans() {
+		let input = "$H2O -> a_b \"·\"·";
+		let spans: Vec<(Token, usize, usize)> = input
+			.chars()
+			.into_token_iter()
+			.map(|SpannedToken { token, span }| (token, span.start, span.end))
+			.collect();
+		assert_eq!(
+			spans,
+			vec![
+				(Cash, 0, 1),
+				(Identifier("H".to_string()), 1, 2),
+				(Integer("2".to_string()), 2, 3),
+				(Identifier("O".to_string()), 3, 4),
+				(Whitespace, 4, 5),
+				(Arrow, 5, 7),
+				(Whitespace, 7, 8),
+				(Identifier("a_b".to_string()), 8, 11),
+				(Whitespace, 11, 12),
+				(StringLiteral("·".to_string()), 12, 15),
+				(Unknown("·".to_string()), 15, 16),
+			]
+		);
+	}
+
+	#[test]
+	fn tokenizes_line_breaks() {
+		let input = "a \t\n b\r\n\nc ";
+		let tokens_are: Vec<Token> = input.chars().into_token_iter().map(|t| t.token).collect();
+		let tokens_should_be = vec![
+			Identifier("a".to_string()),
+			Newline,
+			Identifier("b".to_string()),
+			Newline,
+			Identifier("c".to_string()),
+			Whitespace,
+		];
+		assert_eq!(tokens_are, tokens_should_be);
+	}
+
+	#[test]
+	fn displays_as_written() {
+		let input = "$$Al2 <=> \"x\".y(1.5)";
+		let displayed: String = input
+			.chars()
+			.into_token_iter()
+			.map(|t| t.token.to_string())
+			.collect();
+		assert_eq!(displayed, "$$Al2 <-> \"x\".y(1.5)");
+	}
+
+	#[test]
+	fn tokenizes_arrows() {
+		let input = "-><-><=><=-";
+		let tokens_are: Vec<Token> = input.chars().into_token_iter().map(|t| t.token).collect();
+		let tokens_should_be = vec![
+			Arrow,
+			ReversibleArrow,
+			ReversibleArrow,
+			Unknown("<".to_string()),
+			EqualSign,
+			MinusSign,
+		];
+
+		assert_eq!(tokens_are, tokens_should_be);
+	}
 }